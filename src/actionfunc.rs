@@ -17,17 +17,20 @@ use crate::{
         clone::{DoCloneEvent, on_clone},
         delete::{DoDeleteEvent, on_delete},
         flip::{DoFlipEvent, on_flip},
+        property::{DoAdjustEvent, DoToggleEvent, on_adjust, on_toggle},
         rotate::{DoRotateEvent, on_rotate}
     }
 };
 
-#[derive(Clone, Copy, Debug, Deserialize, Hash, PartialEq, Eq)]
+#[derive(Clone, Debug, Deserialize, Hash, PartialEq, Eq)]
 #[serde(try_from = "String")]
 pub enum ActionFunc {
+    Adjust(String, i64),
     Clone,
     Delete,
     Flip(i32),
-    Rotate(Angle)
+    Rotate(Angle),
+    Toggle(String)
 }
 
 #[derive(Debug, Error)]
@@ -38,6 +41,10 @@ impl TryFrom<String> for ActionFunc {
     type Error = ActionFuncError;
 
     fn try_from(s: String) -> Result<Self, Self::Error> {
+        static ADJUST: LazyLock<Regex> = LazyLock::new(||
+            Regex::new(r#"^(\w+),\s*(-?\d+)\)$"#).expect("bad regex")
+        );
+
         static FLIP: LazyLock<Regex> = LazyLock::new(||
             Regex::new(r#"^(-?\d+)\)$"#).expect("bad regex")
         );
@@ -46,9 +53,19 @@ impl TryFrom<String> for ActionFunc {
             Regex::new(r#"^(-?\d+(\.\d+)?)\)$"#).expect("bad regex")
         );
 
+        static TOGGLE: LazyLock<Regex> = LazyLock::new(||
+            Regex::new(r#"^(\w+)\)$"#).expect("bad regex")
+        );
+
         let (fname, args) = s.split_once('(').unwrap_or((&s, ""));
 
         match fname {
+            "adjust" => ADJUST.captures(args)
+                .and_then(|c| Some(ActionFunc::Adjust(
+                    c.get(1)?.as_str().into(),
+                    c.get(2)?.as_str().parse::<i64>().ok()?
+                )))
+                .ok_or(ActionFuncError(s)),
             "clone" => Ok(ActionFunc::Clone),
             "delete" => Ok(ActionFunc::Delete),
             "flip" => FLIP.captures(args)
@@ -68,6 +85,10 @@ impl TryFrom<String> for ActionFunc {
                     .map(ActionFunc::Rotate)
                 )
                 .ok_or(ActionFuncError(s)),
+            "toggle" => TOGGLE.captures(args)
+                .and_then(|c| c.get(1))
+                .map(|m| ActionFunc::Toggle(m.as_str().into()))
+                .ok_or(ActionFuncError(s)),
             _ => Err(ActionFuncError(s))
         }
     }
@@ -79,10 +100,12 @@ pub fn add_action_observer(
 )
 {
     match action {
+        ActionFunc::Adjust(..) => ec.observe(on_adjust),
         ActionFunc::Clone => ec.observe(on_clone),
         ActionFunc::Delete => ec.observe(on_delete),
         ActionFunc::Flip(_) => ec.observe(on_flip),
-        ActionFunc::Rotate(_) => ec.observe(on_rotate)
+        ActionFunc::Rotate(_) => ec.observe(on_rotate),
+        ActionFunc::Toggle(_) => ec.observe(on_toggle)
     };
 }
 
@@ -101,14 +124,16 @@ where
 
 pub fn trigger_action_func(
     entity: Entity,
-    action: ActionFunc,
+    action: &ActionFunc,
     commands: &mut Commands
 )
 {
     match action {
+        ActionFunc::Adjust(key, delta) => commands.trigger(DoAdjustEvent { entity, key: key.clone(), delta: *delta }),
         ActionFunc::Clone => commands.trigger(DoCloneEvent { entity }),
         ActionFunc::Delete => commands.trigger(DoDeleteEvent { entity } ),
-        ActionFunc::Flip(delta) => commands.trigger(DoFlipEvent { entity, delta } ),
-        ActionFunc::Rotate(dtheta) => commands.trigger(DoRotateEvent { entity, dtheta: dtheta.0 })
+        ActionFunc::Flip(delta) => commands.trigger(DoFlipEvent { entity, delta: *delta } ),
+        ActionFunc::Rotate(dtheta) => commands.trigger(DoRotateEvent { entity, dtheta: dtheta.0 }),
+        ActionFunc::Toggle(key) => commands.trigger(DoToggleEvent { entity, key: key.clone() })
     }
 }
@@ -0,0 +1,251 @@
+use bevy::{
+    asset::io::{AssetReader, AssetReaderError, AssetSourceBuilder, PathStream, Reader, VecReader},
+    ecs::prelude::Resource,
+    tasks::futures_lite::stream
+};
+use itertools::Itertools;
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+    time::SystemTime
+};
+use zip::{ZipArchive, result::ZipError};
+
+use crate::{GameBoxPath, GameBoxPathError, watch::modified};
+
+// the gamebox within an archive
+const ARCHIVE_GAMEBOX: &str = "gamebox.toml";
+
+#[derive(Debug, thiserror::Error)]
+pub enum ArchiveError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("cannot read archive {path:?}: {source}")]
+    Zip {
+        path: PathBuf,
+        source: ZipError
+    },
+    #[error("archive {archive:?} has no {file:?}")]
+    Missing {
+        archive: PathBuf,
+        file: String
+    }
+}
+
+impl ArchiveError {
+    pub fn is_not_found(&self) -> bool {
+        match self {
+            ArchiveError::Io(e) => e.kind() == io::ErrorKind::NotFound,
+            ArchiveError::Missing { .. } => true,
+            ArchiveError::Zip { .. } => false
+        }
+    }
+}
+
+pub fn is_archive(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("zip"))
+}
+
+// zip entries are always separated by slashes
+fn entry_name(path: &Path) -> String {
+    path.components()
+        .filter_map(|c| match c {
+            Component::Normal(s) => Some(s.to_string_lossy()),
+            _ => None
+        })
+        .join("/")
+}
+
+fn open_archive(archive: &Path) -> Result<ZipArchive<File>, ArchiveError> {
+    ZipArchive::new(File::open(archive)?)
+        .map_err(|source| ArchiveError::Zip { path: archive.into(), source })
+}
+
+fn read_zip_entry(
+    zip: &mut ZipArchive<File>,
+    archive: &Path,
+    name: &str
+) -> Result<Vec<u8>, ArchiveError>
+{
+    let mut entry = match zip.by_name(name) {
+        Ok(entry) => entry,
+        Err(ZipError::FileNotFound) => return Err(ArchiveError::Missing {
+            archive: archive.into(),
+            file: name.into()
+        }),
+        Err(source) => return Err(ArchiveError::Zip {
+            path: archive.into(),
+            source
+        })
+    };
+
+    let mut bytes = vec![];
+    entry.read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_entry(archive: &Path, name: &str) -> Result<Vec<u8>, ArchiveError> {
+    read_zip_entry(&mut open_archive(archive)?, archive, name)
+}
+
+// the gamebox TOML, whether loose or packed into an archive
+pub fn read_gamebox(path: &Path) -> Result<String, ArchiveError> {
+    if is_archive(path) {
+        let bytes = read_entry(path, ARCHIVE_GAMEBOX)?;
+        String::from_utf8(bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e).into())
+    }
+    else {
+        Ok(std::fs::read_to_string(path)?)
+    }
+}
+
+// where the files a gamebox refers to are found
+#[derive(Clone, Debug, Resource)]
+pub enum GameBoxFiles {
+    Dir(PathBuf),
+    Zip(PathBuf)
+}
+
+impl GameBoxFiles {
+    pub fn new(gamebox_path: &GameBoxPath) -> Result<Self, GameBoxPathError> {
+        Ok(if is_archive(&gamebox_path.0) {
+            GameBoxFiles::Zip(gamebox_path.0.clone())
+        }
+        else {
+            GameBoxFiles::Dir(gamebox_path.base()?.into())
+        })
+    }
+
+    pub fn read(&self, file: &Path) -> Result<Vec<u8>, ArchiveError> {
+        match self {
+            GameBoxFiles::Dir(base) => Ok(std::fs::read(base.join(file))?),
+            GameBoxFiles::Zip(archive) => read_entry(archive, &entry_name(file))
+        }
+    }
+
+    pub fn asset_source(&self) -> AssetSourceBuilder {
+        match self {
+            GameBoxFiles::Dir(base) => {
+                let base = base.to_string_lossy();
+                AssetSourceBuilder::platform_default(&base, None)
+            },
+            GameBoxFiles::Zip(archive) => {
+                let archive = Arc::new(archive.clone());
+                AssetSourceBuilder::new(move || Box::new(ZipAssetReader::new(archive.clone())))
+            }
+        }
+    }
+}
+
+// an open archive, and its modification time when it was opened
+struct OpenArchive {
+    zip: ZipArchive<File>,
+    modified: Option<SystemTime>
+}
+
+// serves assets out of a zip archive, which is kept open between reads and
+// reopened only once it has been replaced
+pub struct ZipAssetReader {
+    path: Arc<PathBuf>,
+    open: Arc<Mutex<Option<OpenArchive>>>
+}
+
+impl ZipAssetReader {
+    fn new(path: Arc<PathBuf>) -> Self {
+        ZipAssetReader {
+            path,
+            open: Arc::new(Mutex::new(None))
+        }
+    }
+
+    // runs f on the open archive on a blocking thread, since zip reads
+    // would otherwise stall the async executor
+    async fn with_archive<T, F>(&self, f: F) -> Result<T, ArchiveError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut ZipArchive<File>, &Path) -> Result<T, ArchiveError> + Send + 'static
+    {
+        let path = self.path.clone();
+        let open = self.open.clone();
+
+        blocking::unblock(move || {
+            // a read which panicked leaves the archive no worse than it was
+            let mut open = open.lock().unwrap_or_else(PoisonError::into_inner);
+
+            let now = modified(&path);
+            let archive = match open.take() {
+                Some(a) if a.modified == now => a,
+                _ => OpenArchive { zip: open_archive(&path)?, modified: now }
+            };
+
+            f(&mut open.insert(archive).zip, &path)
+        }).await
+    }
+
+    async fn entries(&self) -> Result<Vec<String>, AssetReaderError> {
+        self.with_archive(|zip, _| Ok(zip.file_names().map(str::to_owned).collect()))
+            .await
+            .map_err(reader_error)
+    }
+}
+
+fn reader_error(e: ArchiveError) -> AssetReaderError {
+    match e {
+        ArchiveError::Io(e) => e.into(),
+        e => io::Error::other(e).into()
+    }
+}
+
+impl AssetReader for ZipAssetReader {
+    async fn read<'a>(&'a self, path: &'a Path) -> Result<impl Reader + 'a, AssetReaderError> {
+        let name = entry_name(path);
+
+        match self.with_archive(move |zip, archive| read_zip_entry(zip, archive, &name)).await {
+            Ok(bytes) => Ok(VecReader::new(bytes)),
+            Err(ArchiveError::Missing { .. }) =>
+                Err(AssetReaderError::NotFound(path.into())),
+            Err(e) => Err(reader_error(e))
+        }
+    }
+
+    // there are no .meta files; settings come from the gamebox
+    async fn read_meta<'a>(&'a self, path: &'a Path) -> Result<impl Reader + 'a, AssetReaderError> {
+        Err::<VecReader, _>(AssetReaderError::NotFound(path.into()))
+    }
+
+    async fn read_directory<'a>(
+        &'a self,
+        path: &'a Path
+    ) -> Result<Box<PathStream>, AssetReaderError>
+    {
+        let prefix = format!("{}/", entry_name(path));
+        let prefix = prefix.trim_start_matches('/');
+
+        // the immediate children of the directory
+        let children = self.entries().await?
+            .into_iter()
+            .filter_map(|e| {
+                let rest = e.strip_prefix(prefix)?;
+                let child = rest.split('/').next().filter(|c| !c.is_empty())?;
+                Some(Path::new(prefix).join(child))
+            })
+            .unique()
+            .collect::<Vec<_>>();
+
+        if children.is_empty() {
+            return Err(AssetReaderError::NotFound(path.into()));
+        }
+
+        Ok(Box::new(stream::iter(children)))
+    }
+
+    async fn is_directory<'a>(&'a self, path: &'a Path) -> Result<bool, AssetReaderError> {
+        let prefix = format!("{}/", entry_name(path));
+        Ok(self.entries().await?.iter().any(|e| e.starts_with(&prefix)))
+    }
+}
@@ -1,16 +1,18 @@
 use bevy::{
     asset::{
-        AssetEvent, AssetPath, AssetServer, Handle,
+        AssetEvent, AssetLoadFailedEvent, AssetPath, AssetServer, Handle,
         io::AssetSourceId
     },
     ecs::{
         change_detection::{Res, ResMut},
         error::Result,
+        event::Event,
         message::MessageReader
     },
     image::Image,
-    math::{URect, UVec2},
-    prelude::{AssetId, Assets, Commands, Resource, TextureAtlas, TextureAtlasLayout}
+    math::{IVec2, URect, UVec2},
+    prelude::{AssetId, Assets, Commands, info, Resource, Sprite, TextureAtlas, TextureAtlasLayout, Time, warn},
+    text::Font
 };
 use itertools::Itertools;
 use std::{
@@ -20,7 +22,11 @@ use std::{
 
 use crate::{
     GameBoxPath,
-    gamebox::{GameBox, ImageDefinition}
+    archive::{GameBoxFiles, read_gamebox},
+    gamebox::{GameBox, GameBoxInfo, ImageDefinition, split_cell_key, template},
+    loader::{DEFAULT_DPI, RasterSettings, is_pdf, split_label},
+    raster::{RasterScales, RasterTasks, VectorImage, VectorImages, start_pdf_rasters},
+    watch::FileWatch
 };
 
 #[derive(Clone, Debug)]
@@ -32,48 +38,214 @@ pub enum ImageSource {
     }
 }
 
+impl ImageSource {
+    pub fn sprite(&self) -> Sprite {
+        match self {
+            ImageSource::Single(handle) => Sprite::from_image(handle.clone()),
+            ImageSource::Crop { handle, atlas } => Sprite::from_atlas_image(
+                handle.clone(),
+                atlas.clone()
+            )
+        }
+    }
+}
+
 #[derive(Resource)]
 pub struct SpriteHandles(pub HashMap<String, ImageSource>);
 
 #[derive(Resource)]
 pub struct LoadingHandles(pub HashSet<AssetId<Image>>);
 
+// label fonts by their paths in the gamebox
+#[derive(Resource)]
+pub struct FontHandles(pub HashMap<String, Handle<Font>>);
+
 // TODO: make our own error type for this
-fn load_gamebox(path: &Path) -> Result<GameBox> {
-    let gbs = std::fs::read_to_string(path)?;
-    Ok(toml::from_str(&gbs)?)
+fn load_gamebox(path: &Path) -> Result<(GameBox, GameBoxInfo)> {
+    let gbs = read_gamebox(path)?;
+    let gamebox = template::from_str(&gbs)?;
+    Ok((gamebox, GameBoxInfo::new(path, &gbs)))
 }
 
-pub fn load_assets(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
-    gamebox_path: Res<GameBoxPath>
-) -> Result
+#[derive(Debug, thiserror::Error)]
+pub enum ImageResolveError {
+    #[error("image {image:?}: source {src:?} names no image or grid cell")]
+    UnknownSource {
+        image: String,
+        src: String
+    },
+    #[error("image {0:?}: source chain loops back on itself")]
+    Cycle(String)
+}
+
+// the file an image is drawn from, and where in that file it begins
+#[derive(Clone)]
+struct Region {
+    handle: Handle<Image>,
+    origin: UVec2
+}
+
+// where cell (c, r) of a grid begins, relative to the grid
+fn cell_offset(
+    c: u32,
+    r: u32,
+    cw: u32,
+    rh: u32,
+    cgap: i32,
+    rgap: i32
+) -> IVec2
 {
-    let gamebox = load_gamebox(&gamebox_path.0)?;
+    IVec2::new(
+        c as i32 * (cw as i32 + cgap),
+        r as i32 * (rh as i32 + rgap)
+    )
+}
+
+// resolves images to regions of files, sources before the images
+// derived from them
+struct RegionResolver<'a> {
+    images: &'a HashMap<String, ImageDefinition>,
+    files: &'a HashMap<String, Handle<Image>>,
+    done: HashMap<&'a str, Region>,
+    visiting: HashSet<&'a str>
+}
+
+impl<'a> RegionResolver<'a> {
+    fn new(
+        images: &'a HashMap<String, ImageDefinition>,
+        files: &'a HashMap<String, Handle<Image>>
+    ) -> Self
+    {
+        RegionResolver {
+            images,
+            files,
+            done: HashMap::new(),
+            visiting: HashSet::new()
+        }
+    }
 
-// FIXME: unwrap
-    let base = gamebox_path.0
-        .parent()
-        .unwrap()
-        .to_str()
-        .unwrap()
-        .to_owned();
+    fn image(&mut self, key: &'a str) -> Result<Region, ImageResolveError> {
+        if let Some(region) = self.done.get(key) {
+            return Ok(region.clone());
+        }
 
-    let src = AssetSourceId::from(base);
+        if !self.visiting.insert(key) {
+            return Err(ImageResolveError::Cycle(key.into()));
+        }
 
+        let images = self.images;
+        let region = match &images[key] {
+            ImageDefinition::File(_) |
+            ImageDefinition::Vector { .. } => Region {
+                handle: self.files[key].clone(),
+                origin: UVec2::ZERO
+            },
+            ImageDefinition::Crop { src, x, y, .. } |
+            ImageDefinition::Grid { src, x, y, .. } => {
+                let region = self.source(key, src)?;
+                Region {
+                    origin: region.origin + UVec2::new(*x, *y),
+                    ..region
+                }
+            }
+        };
+
+        self.visiting.remove(key);
+        self.done.insert(key, region.clone());
+        Ok(region)
+    }
+
+    fn source(
+        &mut self,
+        image: &str,
+        src: &'a str
+    ) -> Result<Region, ImageResolveError>
+    {
+        if self.images.contains_key(src) {
+            return self.image(src);
+        }
+
+        // otherwise the source must be a cell of a grid image
+        let images = self.images;
+        if let Some((key, c, r)) = split_cell_key(src)
+            && let Some((key, ImageDefinition::Grid { cols, rows, cw, rh, cgap, rgap, .. })) = images.get_key_value(key)
+            && c < *cols && r < *rows
+        {
+            let grid = self.image(key)?;
+            let offset = cell_offset(c, r, *cw, *rh, *cgap, *rgap);
+            return Ok(Region {
+                origin: (grid.origin.as_ivec2() + offset).as_uvec2(),
+                ..grid
+            });
+        }
+
+        Err(ImageResolveError::UnknownSource {
+            image: image.into(),
+            src: src.into()
+        })
+    }
+}
+
+// the asset path of an image file; a # in it is part of the name
+fn image_asset_path<'a>(f: &'a str, src: &AssetSourceId<'a>) -> AssetPath<'a> {
+    AssetPath::from_path(Path::new(f)).with_source(src.clone())
+}
+
+fn is_pdf_page(f: &str) -> bool {
+    is_pdf(split_label(f).0)
+}
+
+// PDF pages are drawn by raster tasks into handles of their own; a page
+// drawn for the previous gamebox keeps its handle
+fn pdf_page_handle(
+    f: &str,
+    dpi: f32,
+    images: &Assets<Image>,
+    previous: Option<&VectorImages>
+) -> Handle<Image>
+{
+    previous.into_iter()
+        .flat_map(|p| p.0.values())
+        .find(|v| v.pdf && v.file == f && v.dpi == dpi)
+        .map_or_else(|| images.reserve_handle(), |v| v.handle.clone())
+}
+
+fn build_sprite_handles(
+    gamebox: &GameBox,
+    src: &AssetSourceId,
+    asset_server: &AssetServer,
+    images: &Assets<Image>,
+    texture_atlas_layouts: &mut Assets<TextureAtlasLayout>,
+    previous: Option<&VectorImages>
+) -> Result<(SpriteHandles, LoadingHandles, VectorImages), ImageResolveError>
+{
     // begin loading the file-source images
     let files = gamebox.images.iter()
         .filter_map(|(k, v)| match v {
+            ImageDefinition::File(f) if is_pdf_page(f) => Some(
+                (k.clone(), pdf_page_handle(f, DEFAULT_DPI, images, previous))
+            ),
             ImageDefinition::File(f) => Some(
                 (
                     k.clone(),
-                    asset_server.load(
-                        AssetPath::from_path(Path::new(f)).with_source(&src)
-                    )
+                    asset_server.load(image_asset_path(f, src))
                 )
             ),
+            ImageDefinition::Vector { file, dpi } if is_pdf_page(file) => Some(
+                (k.clone(), pdf_page_handle(file, *dpi, images, previous))
+            ),
+            ImageDefinition::Vector { file, dpi } => {
+                let dpi = *dpi;
+                Some(
+                    (
+                        k.clone(),
+                        asset_server.load_with_settings(
+                            image_asset_path(file, src),
+                            move |s: &mut RasterSettings| s.dpi = dpi
+                        )
+                    )
+                )
+            },
             _ => None
         })
         .collect::<HashMap<_, _>>();
@@ -83,97 +255,232 @@ pub fn load_assets(
         .map(|v| v.id())
         .collect::<HashSet<_>>();
 
-    commands.insert_resource(LoadingHandles(lh));
+    let mut sh = files.iter()
+        .map(|(k, v)| (k.clone(), ImageSource::Single(v.clone())))
+        .collect::<HashMap<_, _>>();
 
-// TODO: handle nesting of grid and crop
-    // make texture atlas layouts for the grids
-    let layouts = gamebox.images.iter()
-        .filter_map(|(k, v)| match v {
-            ImageDefinition::Grid { src, x, y, cols, rows, cw, rh, cgap, rgap } => {
-                let x = *x as i32;
-                let y = *y as i32;
-                let cols = *cols as i32;
-                let rows = *rows as i32;
-                let cw = *cw as i32;
-                let rh = *rh as i32;
-
-                let tal = TextureAtlasLayout {
-                    size: UVec2::new(
-                        ((x + cw * cols) + cgap * (cols - 1)) as u32,
-                        ((y + rh * rows) + rgap * (rows - 1)) as u32
-                    ),
-                    textures: (0..rows)
-                        .cartesian_product(0..cols)
-                        .map(|(r, c)|
-                            URect::new(
-                                (x + c * (cw + cgap)) as u32,
-                                (y + r * (rh + rgap)) as u32,
-                                ((x + (c + 1) * cw) + c * cgap) as u32,
-                                ((y + (r + 1) * rh) + r * rgap) as u32
-                            )
-                        )
-                        .collect::<Vec<_>>()
-                };
+    // vector images may be redrawn sharper as the view zooms in
+    let mut vi = gamebox.images.iter()
+        .filter_map(|(k, v)| {
+            let (file, dpi) = match v {
+                ImageDefinition::Vector { file, dpi } => (file, *dpi),
+                ImageDefinition::File(f) if is_pdf_page(f) => (f, DEFAULT_DPI),
+                _ => return None
+            };
 
-                Some((k, texture_atlas_layouts.add(tal)))
-            },
-            _ => None
+            Some((
+                files[k].id(),
+                VectorImage {
+                    handle: files[k].clone(),
+                    file: file.clone(),
+                    dpi,
+                    pdf: is_pdf_page(file),
+                    layouts: vec![]
+                }
+            ))
         })
         .collect::<HashMap<_, _>>();
 
-    // add the derived assets to the image sources
-    let derived = gamebox.images.iter()
-        .flat_map(|(k, v)| match v {
-            ImageDefinition::Crop { src, x, y, w, h } => {
-                let tal = TextureAtlasLayout {
-                    size: UVec2::new(x + w, y + h),
-                    textures: vec![URect::new(*x, *y, x + w, y + h)]
+    // crops and grids may be taken from any other image, so each is
+    // resolved to a region of a file and becomes an atlas over that file
+    let mut resolver = RegionResolver::new(&gamebox.images, &files);
+
+    for (k, v) in &gamebox.images {
+        match v {
+            ImageDefinition::File(_) |
+            ImageDefinition::Vector { .. } => {},
+            ImageDefinition::Crop { w, h, .. } => {
+                let Region { handle, origin } = resolver.image(k)?;
+
+                let rect = URect::from_corners(origin, origin + UVec2::new(*w, *h));
+                let base = TextureAtlasLayout {
+                    size: rect.max,
+                    textures: vec![rect]
                 };
-                let layout = texture_atlas_layouts.add(tal);
+                let layout = texture_atlas_layouts.add(base.clone());
 
-                vec![
-                    (
-                        k.clone(),
-                        ImageSource::Crop {
-                            handle: files.get(src).unwrap().clone(),
-                            atlas: TextureAtlas { layout, index: 0 }
-                        }
-                    )
-                ]
+                if let Some(v) = vi.get_mut(&handle.id()) {
+                    v.layouts.push((layout.clone(), base));
+                }
+
+                sh.insert(
+                    k.clone(),
+                    ImageSource::Crop {
+                        handle,
+                        atlas: TextureAtlas { layout, index: 0 }
+                    }
+                );
             },
-            ImageDefinition::Grid { src, cols, rows, .. } => {
-                (0..*rows)
+            ImageDefinition::Grid { cols, rows, cw, rh, cgap, rgap, .. } => {
+                let Region { handle, origin } = resolver.image(k)?;
+
+                let textures = (0..*rows)
                     .cartesian_product(0..*cols)
-                    .map(|(r, c)|
-                        (
-                            format!("{k}@{c},{r}"),
-                            ImageSource::Crop {
-                                handle: files.get(src).unwrap().clone(),
-                                atlas: TextureAtlas {
-                                    layout: layouts.get(k).unwrap().clone(),
-                                    index: (r * cols + c) as usize
+                    .map(|(r, c)| {
+                        let o = (
+                            origin.as_ivec2() +
+                            cell_offset(c, r, *cw, *rh, *cgap, *rgap)
+                        ).as_uvec2();
+                        URect::from_corners(o, o + UVec2::new(*cw, *rh))
+                    })
+                    .collect::<Vec<_>>();
+
+                let base = TextureAtlasLayout {
+                    size: textures.iter().fold(UVec2::ZERO, |s, t| s.max(t.max)),
+                    textures
+                };
+                let layout = texture_atlas_layouts.add(base.clone());
+
+                if let Some(v) = vi.get_mut(&handle.id()) {
+                    v.layouts.push((layout.clone(), base));
+                }
+
+                sh.extend(
+                    (0..*rows)
+                        .cartesian_product(0..*cols)
+                        .map(|(r, c)|
+                            (
+                                format!("{k}@{c},{r}"),
+                                ImageSource::Crop {
+                                    handle: handle.clone(),
+                                    atlas: TextureAtlas {
+                                        layout: layout.clone(),
+                                        index: (r * cols + c) as usize
+                                    }
                                 }
-                            }
+                            )
                         )
-                    )
-                    .collect::<Vec<_>>()
-            },
-            _ => vec![]
-        });
+                );
+            }
+        }
+    }
 
-    let sh = files.iter()
-        .map(|(k, v)| (k.clone(), ImageSource::Single(v.clone())))
-        .chain(derived)
-        .collect::<HashMap<_, _>>();
+    Ok((SpriteHandles(sh), LoadingHandles(lh), VectorImages(vi)))
+}
 
-    commands.insert_resource(SpriteHandles(sh));
+fn build_font_handles(
+    gamebox: &GameBox,
+    src: &AssetSourceId,
+    asset_server: &AssetServer
+) -> FontHandles
+{
+    FontHandles(
+        gamebox.piece.values()
+            .flat_map(|p| &p.labels)
+            .filter_map(|l| l.font.as_ref())
+            .unique()
+            .map(|f| (
+                f.clone(),
+                asset_server.load(
+                    AssetPath::from_path(Path::new(f)).with_source(src.clone())
+                )
+            ))
+            .collect()
+    )
+}
+
+pub fn load_assets(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    images: Res<Assets<Image>>,
+    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    gamebox_path: Res<GameBoxPath>,
+    files: Res<GameBoxFiles>,
+    scales: Res<RasterScales>,
+    mut tasks: ResMut<RasterTasks>
+) -> Result
+{
+    let (gamebox, gamebox_info) = load_gamebox(&gamebox_path.0)?;
+
+    let src = AssetSourceId::from(gamebox_path.base()?);
+
+    let (sprite_handles, loading_handles, vector_images) = build_sprite_handles(
+        &gamebox,
+        &src,
+        &asset_server,
+        &images,
+        &mut texture_atlas_layouts,
+        None
+    )?;
+
+    start_pdf_rasters(&vector_images, &scales, &files, &mut tasks);
+
+    let font_handles = build_font_handles(&gamebox, &src, &asset_server);
+
+    commands.insert_resource(loading_handles);
+    commands.insert_resource(sprite_handles);
+    commands.insert_resource(vector_images);
+    commands.insert_resource(font_handles);
+    commands.insert_resource(gamebox);
+    commands.insert_resource(gamebox_info);
+
+    Ok(())
+}
+
+#[derive(Resource)]
+pub struct GameBoxWatch(pub FileWatch);
+
+#[derive(Event)]
+pub struct GameBoxReloaded;
+
+pub fn reload_gamebox(
+    time: Res<Time>,
+    mut watch: ResMut<GameBoxWatch>,
+    gamebox_path: Res<GameBoxPath>,
+    asset_server: Res<AssetServer>,
+    images: Res<Assets<Image>>,
+    mut texture_atlas_layouts: ResMut<Assets<TextureAtlasLayout>>,
+    files: Res<GameBoxFiles>,
+    previous: Res<VectorImages>,
+    scales: Res<RasterScales>,
+    mut tasks: ResMut<RasterTasks>,
+    mut commands: Commands
+) -> Result
+{
+    if !watch.0.poll(time.delta()) {
+        return Ok(());
+    }
+
+    let (gamebox, gamebox_info) = match load_gamebox(&gamebox_path.0) {
+        Ok(gb) => gb,
+        Err(e) => {
+            warn!("keeping the previous gamebox: {e}");
+            return Ok(());
+        }
+    };
+
+    let src = AssetSourceId::from(gamebox_path.base()?);
+
+    // images which are already loaded are not loaded again
+    let (sprite_handles, _, vector_images) = build_sprite_handles(
+        &gamebox,
+        &src,
+        &asset_server,
+        &images,
+        &mut texture_atlas_layouts,
+        Some(&previous)
+    )?;
+
+    start_pdf_rasters(&vector_images, &scales, &files, &mut tasks);
+
+    let font_handles = build_font_handles(&gamebox, &src, &asset_server);
+
+    commands.insert_resource(sprite_handles);
+    commands.insert_resource(vector_images);
+    commands.insert_resource(font_handles);
     commands.insert_resource(gamebox);
+    commands.insert_resource(gamebox_info);
+
+    // pieces and grids rebuild themselves from the new gamebox
+    info!("reloaded gamebox");
+    commands.trigger(GameBoxReloaded);
 
     Ok(())
 }
 
 pub fn mark_images_loaded(
     mut asset_events: MessageReader<AssetEvent<Image>>,
+    mut failed_events: MessageReader<AssetLoadFailedEvent<Image>>,
     mut loading_handles: ResMut<LoadingHandles>
 )
 {
@@ -183,4 +490,11 @@ pub fn mark_images_loaded(
             eprint!(".");
         }
     }
+
+    // an image which cannot be loaded should not hold up the game
+    for e in failed_events.read() {
+        if loading_handles.0.remove(&e.id) {
+            warn!("cannot load {}: {}", e.path, e.error);
+        }
+    }
 }
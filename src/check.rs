@@ -0,0 +1,135 @@
+use bevy::{
+    MinimalPlugins,
+    app::{App, AppExit, Startup, Update},
+    asset::{AssetApp, AssetPlugin},
+    ecs::{
+        error::{BevyError, ErrorContext, Result},
+        message::MessageWriter,
+        name::Name,
+        prelude::{Commands, IntoScheduleConfigs, Query, RelationshipTarget, With}
+    },
+    image::{ImagePlugin, TextureAtlasPlugin},
+    mesh::Mesh,
+    prelude::{ColorMaterial, TransformPlugin},
+    text::{Font, FontLoader}
+};
+
+use crate::{
+    GameBoxPath, GameBoxPathError, LogPath,
+    archive::GameBoxFiles,
+    assets::load_assets,
+    edit_plugin,
+    loader::loader_plugin,
+    log::{init_log, RedoAllEvent},
+    log_deserialize::{deserialize_edits, update_next_object_id},
+    log_validate::validate_edits,
+    object::ObjectId,
+    piece::{Above, Angle, Below, Faces, FaceUp, Location, Piece, PieceTypeId},
+    raster::{RasterScales, RasterTasks},
+    recovery::{recovery_path, RecoveryPath, ReplayRecovery},
+    setup_game_resources
+};
+
+// there is nobody to show a failure to, so any failure ends the check
+fn exit_on_error(err: BevyError, ctx: ErrorContext) {
+    eprintln!("{ctx}: {err}");
+    std::process::exit(1);
+}
+
+fn replay_edits(mut commands: Commands) {
+    commands.trigger(RedoAllEvent);
+}
+
+fn print_pieces(
+    piece_query: Query<(&ObjectId, &PieceTypeId, &Name, &Above, &Location, &Angle, &FaceUp, &Faces, Option<&Below>), With<Piece>>,
+    oid_query: Query<&ObjectId>,
+    mut exit: MessageWriter<AppExit>
+) -> Result
+{
+    let mut pieces = piece_query.iter().collect::<Vec<_>>();
+    pieces.sort_by_key(|p| p.0.0);
+
+    for (oid, type_id, name, above, loc, angle, up, faces, below) in pieces {
+        let parent = oid_query.get(above.0)?.0;
+
+        let below = below.iter()
+            .flat_map(|b| b.iter())
+            .map(|e| oid_query.get(e).map(|oid| oid.0.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        println!(
+            "piece {} {:?} type {} above {} at ({}, {}, {}) angle {} face {}/{} below [{}]",
+            oid.0,
+            name.as_str(),
+            type_id.0,
+            parent,
+            loc.0.x,
+            loc.0.y,
+            loc.0.z,
+            angle.0,
+            up.0,
+            faces.0.len(),
+            below.join(", ")
+        );
+    }
+
+    exit.write(AppExit::Success);
+    Ok(())
+}
+
+pub fn run_check(
+    gamebox_path: GameBoxPath,
+    log_path: LogPath
+) -> Result<AppExit, GameBoxPathError>
+{
+    let base_path = gamebox_path.base()?;
+    let gamebox_files = GameBoxFiles::new(&gamebox_path)?;
+
+    let exit = App::new()
+        .set_error_handler(exit_on_error)
+        .insert_resource(RecoveryPath(recovery_path(&gamebox_path.0)))
+        .insert_resource(ReplayRecovery(false))
+        .insert_resource(gamebox_path)
+        .insert_resource(log_path)
+        .insert_resource(gamebox_files.clone())
+        .init_resource::<RasterScales>()
+        .init_resource::<RasterTasks>()
+        .register_asset_source(
+            base_path.clone(),
+            gamebox_files.asset_source()
+        )
+        .add_plugins((
+            MinimalPlugins,
+            AssetPlugin::default(),
+            ImagePlugin::default(),
+            TextureAtlasPlugin,
+            TransformPlugin
+        ))
+        .init_asset::<Mesh>()
+        .init_asset::<ColorMaterial>()
+        // label fonts are loaded with the images, though never drawn here
+        .init_asset::<Font>()
+        .init_asset_loader::<FontLoader>()
+        .add_plugins((loader_plugin, edit_plugin))
+        .add_systems(
+            Startup,
+            (
+                setup_game_resources,
+                load_assets,
+                init_log,
+                deserialize_edits
+                    .after(load_assets)
+                    .after(init_log),
+                validate_edits
+                    .after(deserialize_edits),
+                update_next_object_id
+                    .after(validate_edits),
+                replay_edits
+                    .after(update_next_object_id)
+            )
+        )
+        .add_systems(Update, print_pieces)
+        .run();
+
+    Ok(exit)
+}
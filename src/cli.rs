@@ -0,0 +1,63 @@
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[command(version, about = "V4 Bevy Demo", subcommand_negates_reqs = true)]
+pub struct Cli {
+    /// The gamebox to load
+    #[arg(long, value_name = "FILE", required = true)]
+    pub gamebox: Option<PathBuf>,
+
+    /// A saved log to replay
+    #[arg(long, value_name = "FILE")]
+    pub log: Option<PathBuf>,
+
+    /// The scenario to start from when not replaying a log
+    #[arg(long, value_name = "NAME", conflicts_with = "log")]
+    pub scenario: Option<String>,
+
+    /// A configuration file overriding the user and gamebox ones
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Go straight to the game once the gamebox has loaded
+    #[arg(long)]
+    pub no_splash: bool,
+
+    /// The initial window size, in pixels
+    #[arg(long, value_name = "WxH", value_parser = parse_window_size)]
+    pub window_size: Option<(u32, u32)>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Replay a log without a window and print the resulting pieces
+    Check {
+        /// The gamebox the log was made with
+        gamebox: PathBuf,
+        /// The log to replay
+        log: PathBuf
+    },
+    /// Report every problem found in a gamebox and its images
+    Lint {
+        /// The gamebox to check
+        gamebox: PathBuf
+    }
+}
+
+fn parse_window_size(s: &str) -> Result<(u32, u32), String> {
+    let (w, h) = s.split_once(['x', 'X'])
+        .ok_or_else(|| format!("expected WIDTHxHEIGHT, found {s:?}"))?;
+
+    let w = w.parse::<u32>().map_err(|e| format!("bad width {w:?}: {e}"))?;
+    let h = h.parse::<u32>().map_err(|e| format!("bad height {h:?}: {e}"))?;
+
+    if w == 0 || h == 0 {
+        return Err("window size must be nonzero".into());
+    }
+
+    Ok((w, h))
+}
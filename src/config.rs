@@ -1,15 +1,27 @@
 use bevy::{
     ecs::{
+        change_detection::{Res, ResMut},
         error::Result,
         prelude::Commands
     },
-    prelude::Resource
+    input::keyboard::KeyCode,
+    prelude::{debug, info, Resource, Time, warn}
 };
 use serde::Deserialize;
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::Duration
+};
+use toml::{Table, Value};
 
-use crate::keys::KeyBinding;
+use crate::{
+    keys::{Handedness, KeyBinding, Modifiers},
+    watch::FileWatch
+};
 
 #[derive(Debug, Deserialize)]
+#[serde(default)]
 pub struct Steps {
     pub pan_step: f32,
     pub rotate_step: f32,
@@ -17,7 +29,19 @@ pub struct Steps {
     pub wheel_scale_step: f32
 }
 
+impl Default for Steps {
+    fn default() -> Self {
+        Steps {
+            pan_step: 10.0,
+            rotate_step: 1.0,
+            key_scale_step: 0.02,
+            wheel_scale_step: 0.1
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
 pub struct Keys {
     pub pan_left: KeyBinding,
     pub pan_right: KeyBinding,
@@ -29,26 +53,204 @@ pub struct Keys {
     pub rotate_ccw: KeyBinding,
     pub rotate_cw: KeyBinding,
     pub undo: KeyBinding,
-    pub redo: KeyBinding
+    pub redo: KeyBinding,
+    pub save: KeyBinding,
+    pub save_as: KeyBinding
+}
+
+fn key(code: KeyCode) -> KeyBinding {
+    KeyBinding { code, modifiers: Modifiers::default() }
+}
+
+fn ctrl(code: KeyCode) -> KeyBinding {
+    KeyBinding {
+        code,
+        modifiers: Modifiers {
+            ctrl_key: Some(Handedness::Either),
+            ..Default::default()
+        }
+    }
+}
+
+fn ctrl_shift(code: KeyCode) -> KeyBinding {
+    KeyBinding {
+        code,
+        modifiers: Modifiers {
+            ctrl_key: Some(Handedness::Either),
+            shift_key: Some(Handedness::Either),
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for Keys {
+    fn default() -> Self {
+        Keys {
+            pan_left: key(KeyCode::ArrowLeft),
+            pan_right: key(KeyCode::ArrowRight),
+            pan_up: key(KeyCode::ArrowUp),
+            pan_down: key(KeyCode::ArrowDown),
+            zoom_in: key(KeyCode::Equal),
+            zoom_out: key(KeyCode::Minus),
+            zoom_reset: key(KeyCode::Digit0),
+            rotate_ccw: key(KeyCode::BracketLeft),
+            rotate_cw: key(KeyCode::BracketRight),
+            undo: ctrl(KeyCode::KeyZ),
+            redo: ctrl(KeyCode::KeyY),
+            save: ctrl(KeyCode::KeyS),
+            save_as: ctrl_shift(KeyCode::KeyS)
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(default)]
 pub struct Mouse {
     pub double_click_threshold: u32
 }
 
-#[derive(Debug, Deserialize, Resource)]
+impl Default for Mouse {
+    fn default() -> Self {
+        Mouse {
+            double_click_threshold: 300
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct Log {
+    // save edits past the cursor, and the cursor itself
+    #[serde(default)]
+    pub keep_redo: bool
+}
+
+#[derive(Debug, Default, Deserialize, Resource)]
+#[serde(default)]
 pub struct Config {
     pub steps: Steps,
     pub keys: Keys,
-    pub mouse: Mouse
+    pub mouse: Mouse,
+    pub log: Log
+}
+
+#[derive(Clone, Debug)]
+pub struct ConfigSource {
+    pub path: PathBuf,
+    // a missing optional source is skipped
+    pub required: bool
+}
+
+// config files, from lowest to highest precedence
+#[derive(Clone, Debug, Resource)]
+pub struct ConfigSources(pub Vec<ConfigSource>);
+
+impl ConfigSources {
+    pub fn new(gamebox_path: &Path, config_path: Option<PathBuf>) -> Self {
+        let user = dirs::config_dir()
+            .map(|d| d.join("bevy_demo").join("config.toml"));
+
+        let mut gamebox = gamebox_path.as_os_str().to_owned();
+        gamebox.push(".config.toml");
+
+        // config.toml in the working directory was once the only config,
+        // so it still comes between the user's and the gamebox's
+        ConfigSources(
+            user.into_iter()
+                .chain([PathBuf::from("config.toml"), PathBuf::from(gamebox)])
+                .map(|path| ConfigSource { path, required: false })
+                .chain(config_path.map(|path| ConfigSource { path, required: true }))
+                .collect()
+        )
+    }
+
+    pub fn watch(&self) -> ConfigWatch {
+        ConfigWatch(FileWatch::new(
+            self.0.iter().map(|s| s.path.clone()).collect(),
+            Duration::from_secs(1)
+        ))
+    }
 }
 
-pub fn load_config(mut commands: Commands) -> Result {
-    let config_str = std::fs::read_to_string("config.toml")?;
-    let config: Config = toml::from_str(&config_str)?;
+#[derive(Resource)]
+pub struct ConfigWatch(pub FileWatch);
 
-    commands.insert_resource(config);
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    #[error("cannot read config {path:?}: {source}")]
+    Read {
+        path: PathBuf,
+        source: io::Error
+    },
+    #[error("cannot parse config {path:?}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: toml::de::Error
+    },
+    #[error("invalid config: {0}")]
+    Invalid(#[from] toml::de::Error)
+}
+
+// overlay the keys set in over onto base
+pub fn merge(base: &mut Table, over: Table) {
+    for (k, v) in over {
+        match (base.get_mut(&k), v) {
+            (Some(Value::Table(b)), Value::Table(o)) => merge(b, o),
+            (_, v) => { base.insert(k, v); }
+        }
+    }
+}
 
+pub fn read_config(sources: &ConfigSources) -> Result<Config, ConfigError> {
+    let mut merged = Table::new();
+
+    for ConfigSource { path, required } in &sources.0 {
+        let s = match std::fs::read_to_string(path) {
+            Ok(s) => s,
+            Err(e) if !required && e.kind() == io::ErrorKind::NotFound => continue,
+            Err(source) => return Err(ConfigError::Read {
+                path: path.clone(),
+                source
+            })
+        };
+
+        let table = toml::from_str::<Table>(&s)
+            .map_err(|source| ConfigError::Parse {
+                path: path.clone(),
+                source
+            })?;
+
+        debug!("read config {}", path.display());
+        merge(&mut merged, table);
+    }
+
+    Ok(Value::Table(merged).try_into()?)
+}
+
+pub fn load_config(
+    sources: Res<ConfigSources>,
+    mut commands: Commands
+) -> Result
+{
+    commands.insert_resource(read_config(&sources)?);
     Ok(())
 }
+
+pub fn reload_config(
+    time: Res<Time>,
+    mut watch: ResMut<ConfigWatch>,
+    sources: Res<ConfigSources>,
+    mut commands: Commands
+)
+{
+    if !watch.0.poll(time.delta()) {
+        return;
+    }
+
+    match read_config(&sources) {
+        Ok(config) => {
+            info!("reloaded config");
+            commands.insert_resource(config);
+        },
+        Err(e) => warn!("keeping the previous config: {e}")
+    }
+}
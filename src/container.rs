@@ -0,0 +1,156 @@
+use bevy::{
+    asset::Assets,
+    ecs::{
+        change_detection::{Res, ResMut},
+        component::Component,
+        entity::Entity,
+        name::Name,
+        observer::On,
+        prelude::{Changed, ChildOf, Commands, Or, Query, RelationshipTarget, With}
+    },
+    math::{
+        Quat, Vec2, Vec3,
+        prelude::Rectangle
+    },
+    mesh::{Mesh, Mesh2d},
+    picking::Pickable,
+    prelude::{Color, ColorMaterial, MeshMaterial2d, trace, Transform, Visibility, warn}
+};
+use itertools::Itertools;
+use tracing::instrument;
+
+use crate::{
+    assets::GameBoxReloaded,
+    drag::handle_drop,
+    gamebox::{ContainerDefinition, GameBox, Layout},
+    grid::anchor_to_vec3,
+    object::ObjectId,
+    piece::{Above, Below, Location, Piece}
+};
+
+pub mod create;
+
+#[derive(Clone, Component, Copy, Debug, Default)]
+pub struct Container;
+
+#[derive(Clone, Component, Copy, Debug)]
+pub struct ContainerTypeId(pub u32);
+
+pub fn container_transform(c: &ContainerDefinition) -> Transform {
+    use std::f32::consts::PI;
+
+    let rect = Rectangle::new(c.w, c.h);
+
+    // containers lie on the surface like grids do
+    Transform {
+        translation: Vec3::new(c.x, c.y, 1.0) + anchor_to_vec3(rect, c.anchor),
+        rotation: Quat::from_rotation_z(c.a * PI / 180.0),
+        scale: Vec3::new(c.s, c.s, 1.0)
+    }
+}
+
+// where the ith piece in a container goes, relative to its center
+fn slot(c: &ContainerDefinition, i: usize) -> Vec3 {
+    // the first slot is in the top left corner
+    let first = Vec2::new(
+        (c.spacing - c.w) / 2.0,
+        (c.h - c.spacing) / 2.0
+    );
+
+    let i = i as u32;
+
+    let xy = match c.layout {
+        Layout::Row => first + Vec2::new(i as f32 * c.spacing, 0.0),
+        Layout::Column => first - Vec2::new(0.0, i as f32 * c.spacing),
+        Layout::Grid => first + Vec2::new(
+            (i % c.cols) as f32 * c.spacing,
+            -((i / c.cols) as f32) * c.spacing
+        ),
+        // offset like a stack
+        Layout::Pile => Vec2::splat(2.0 * i as f32)
+    };
+
+    xy.extend(1.0 + i as f32)
+}
+
+pub fn spawn_container(
+    oid: u32,
+    c: &ContainerDefinition,
+    parent: Entity,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<ColorMaterial>>,
+    commands: &mut Commands
+)
+{
+    let fill_color = Color::srgba(0.5, 0.5, 0.5, 0.3);
+
+    commands.spawn((
+        Container,
+        ObjectId(oid),
+        ContainerTypeId(c.id),
+        Name::from(c.name.as_ref()),
+        Mesh2d(meshes.add(Rectangle::new(c.w, c.h))),
+        MeshMaterial2d(materials.add(fill_color)),
+        ChildOf(parent),
+        container_transform(c),
+        Pickable::default(),
+        Visibility::Inherited
+    ))
+    .observe(handle_drop);
+}
+
+#[instrument(skip_all)]
+pub fn refresh_containers(
+    _evt: On<GameBoxReloaded>,
+    query: Query<(Entity, &ObjectId, &ContainerTypeId), With<Container>>,
+    gamebox: Res<GameBox>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut commands: Commands
+)
+{
+    trace!("");
+
+    for (entity, oid, tid) in query {
+        let Some(c) = gamebox.container.get(&tid.0) else {
+            warn!("container type {} was removed; keeping container {}", tid.0, oid.0);
+            continue;
+        };
+
+        // the container is updated in place so that what is in it stays put;
+        // reinserting the type id rearranges its pieces
+        commands.entity(entity).insert((
+            ContainerTypeId(c.id),
+            Name::from(c.name.as_ref()),
+            Mesh2d(meshes.add(Rectangle::new(c.w, c.h))),
+            container_transform(c)
+        ));
+    }
+}
+
+// lays out the pieces in containers which have gained, lost, or moved one
+#[instrument(skip_all)]
+pub fn arrange_containers(
+    changed_query: Query<Entity, (With<Container>, Or<(Changed<Below>, Changed<ContainerTypeId>)>)>,
+    moved_query: Query<&Above, (With<Piece>, Changed<Location>)>,
+    container_query: Query<(&ContainerTypeId, Option<&Below>), With<Container>>,
+    mut t_query: Query<&mut Transform, With<Piece>>,
+    gamebox: Res<GameBox>
+)
+{
+    trace!("");
+
+    let containers = changed_query.iter()
+        .chain(moved_query.iter().map(|a| a.0))
+        .unique();
+
+    for c in containers {
+        let Ok((tid, below)) = container_query.get(c) else { continue; };
+        let Some(cdef) = gamebox.container.get(&tid.0) else { continue; };
+
+        for (i, e) in below.iter().flat_map(|b| b.iter()).enumerate() {
+            if let Ok(mut t) = t_query.get_mut(e) {
+                t.translation = slot(cdef, i);
+            }
+        }
+    }
+}
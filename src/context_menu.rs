@@ -140,7 +140,7 @@ fn make_context_item(
 )
 {
     let mut item = commands.spawn((
-        ContextMenuItem(action.action),
+        ContextMenuItem(action.action.clone()),
         Button,
         Node {
             padding: UiRect::all(px(5)),
@@ -222,10 +222,10 @@ fn on_item_selection(
         let ei = query.iter();
         match ei.len() {
             0 => {},
-            1 => ei.for_each(|e| trigger_action_func(e, item.0, &mut commands)),
+            1 => ei.for_each(|e| trigger_action_func(e, &item.0, &mut commands)),
             _ => {
                 commands.trigger(OpenGroupEvent);
-                ei.for_each(|e| trigger_action_func(e, item.0, &mut commands));
+                ei.for_each(|e| trigger_action_func(e, &item.0, &mut commands));
                 commands.trigger(CloseGroupEvent);
             }
         }
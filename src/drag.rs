@@ -7,7 +7,7 @@ use bevy::{
         error::Result,
         name::Name,
         observer::On,
-        prelude::{ChildOf, Commands, Entity, Or, Query, Resource, With, Without}
+        prelude::{ChildOf, Commands, Entity, Has, Or, Query, Resource, With, Without}
     },
     image::Image,
     input::{
@@ -21,16 +21,18 @@ use bevy::{
         mesh_picking::ray_cast::{MeshRayCast, MeshRayCastSettings},
         pointer::PointerButton
     },
-    prelude::{Color, GlobalTransform, Projection, Sprite, State, trace, Transform},
+    prelude::{Color, GlobalTransform, Projection, Sprite, State, TextureAtlasLayout, trace, Transform},
     sprite::Anchor
 };
 use tracing::instrument;
 
 use crate::{
+    container::Container,
     context_menu::ContextMenuState,
     grid::{HexGridCell, RectGridCell},
     keys::{ctrl_pressed, shift_pressed},
     log::{OpenGroupEvent, CloseGroupEvent},
+    map::Map,
     maxz::MaxZ,
     piece::{
         Above, Below, Location, StackingGroup,
@@ -184,8 +186,10 @@ pub fn on_piece_drag_end(
 #[derive(Clone, Copy, Debug)]
 enum DropTargetType {
     Surface,
+    Map,
     Piece,
-    Grid
+    Grid,
+    Container
 }
 
 fn find_hit(
@@ -196,9 +200,11 @@ fn find_hit(
     drag_dist: Vec3,
     max_z: f32,
     dz: f32,
-    bboxes: &[(Entity, f32, Entity, Rect)],
+    bboxes: &[(Entity, f32, Entity, Rect, DropTargetType)],
     ray_cast: &mut MeshRayCast,
     mrcs: &MeshRayCastSettings,
+    container_query: &Query<(), With<Container>>,
+    a_query: &Query<(Option<&Above>, &StackingGroup)>,
     root: Entity,
     gt_query: Query<&GlobalTransform>,
     sg_query: Query<&StackingGroup>,
@@ -206,28 +212,49 @@ fn find_hit(
 {
     let b_drop_pos = (src_gt.translation() + drag_dist).truncate();
 
-    // find piece hits
-    let pieces = bboxes.iter()
-        .filter(|(_, _, base, bb)| esrc != *base && bb.contains(b_drop_pos))
-        .map(|(e, z, ..)| (*e, *z, DropTargetType::Piece));
+    // find piece and map hits
+    let sprites = bboxes.iter()
+        .filter(|(_, _, base, bb, _)| esrc != *base && bb.contains(b_drop_pos))
+        .map(|(e, z, base, _, t)| (*e, *z, *base, *t));
 
-    // find grid cell hits
+    // find grid cell and container hits
     let ray = Ray3d::new(b_drop_pos.extend(max_z + 1.0), Dir3::NEG_Z);
     let cells = ray_cast.cast_ray(ray, mrcs)
         .iter()
-        .map(|(e, h)| (*e, h.point.z, DropTargetType::Grid));
+        .map(|(e, h)| (
+            *e,
+            h.point.z,
+            *e,
+            if container_query.contains(*e) {
+                DropTargetType::Container
+            }
+            else {
+                DropTargetType::Grid
+            }
+        ));
 
     // surface is always a hit
-    let surf = (root, f32::NEG_INFINITY, DropTargetType::Surface);
+    let surf = (root, f32::NEG_INFINITY, root, DropTargetType::Surface);
 
     // find top hit
-    let (ehit, htype) = std::iter::once(surf)
-        .chain(pieces)
+    let (ehit, base, htype) = std::iter::once(surf)
+        .chain(sprites)
         .chain(cells)
-        .max_by(|(_, za, _), (_, zb, _)| za.partial_cmp(zb).expect("NaN"))
-        .map(|(e, _, t)| (e, t))
+        .max_by(|(_, za, _, _), (_, zb, _, _)| za.partial_cmp(zb).expect("NaN"))
+        .map(|(e, _, b, t)| (e, b, t))
         .expect("Surface will be hit if nothing else is");
 
+    // a container arranges its pieces itself, so dropping onto a piece in
+    // a container drops into the container
+    let (ehit, htype) = match htype {
+        DropTargetType::Piece => match a_query.get(base)? {
+            (Some(Above(c)), _) if container_query.contains(*c) =>
+                (*c, DropTargetType::Container),
+            _ => (ehit, htype)
+        },
+        _ => (ehit, htype)
+    };
+
     let dst_t = match htype {
         DropTargetType::Surface => {
             if parent == ehit {
@@ -238,6 +265,15 @@ fn find_hit(
                 src_gt.reparented_to(dst_gt)
             }.translation + drag_dist
         },
+        DropTargetType::Map => {
+            // maps may be scaled or rotated, so move in world space first
+            let dst_gt = gt_query.get(ehit)?;
+            let moved = GlobalTransform::from(
+                src_gt.compute_transform()
+                    .with_translation(src_gt.translation() + drag_dist)
+            );
+            moved.reparented_to(dst_gt).translation
+        },
         DropTargetType::Piece => {
             let src_sg = sg_query.get(esrc)?;
             let dst_sg = sg_query.get(ehit)?;
@@ -256,12 +292,34 @@ fn find_hit(
         DropTargetType::Grid => {
             // snap piece to center of grid cell
             Vec3::new(0.0, 0.0, dz)
+        },
+        DropTargetType::Container => {
+            // the container arranges its pieces itself
+            Vec3::new(0.0, 0.0, dz)
         }
     };
 
     Ok((ehit, dst_t))
 }
 
+// the size a sprite is drawn at, which for a redrawn vector image is not
+// the size of its image
+fn sprite_size(
+    s: &Sprite,
+    images: &Assets<Image>,
+    layouts: &Assets<TextureAtlasLayout>
+) -> Option<Vec2>
+{
+    if let Some(size) = s.custom_size {
+        return Some(size);
+    }
+
+    match &s.texture_atlas {
+        Some(atlas) => atlas.texture_rect(layouts).map(|r| r.size().as_vec2()),
+        None => images.get(&s.image).map(Image::size_f32)
+    }
+}
+
 #[instrument(skip_all)]
 pub fn handle_drop(
     mut drop: On<Pointer<DragDrop>>,
@@ -273,11 +331,13 @@ pub fn handle_drop(
     drag_origin: Res<DragOrigin>,
     gt_query: Query<&GlobalTransform>,
     sg_query: Query<&StackingGroup>,
-    sprite_collision_query: Query<(Entity, &GlobalTransform, &Anchor, &Sprite, &Name)>,
+    sprite_collision_query: Query<(Entity, &GlobalTransform, &Anchor, &Sprite, Has<Map>), With<Name>>,
     mut ray_cast: MeshRayCast,
-    cell_query: Query<(), Or<(With<HexGridCell>, With<RectGridCell>)>>,
+    cell_query: Query<(), Or<(With<HexGridCell>, With<RectGridCell>, With<Container>)>>,
+    container_query: Query<(), With<Container>>,
 //    mesh_collision_query: Query<(Entity, &GlobalTransform)>,
     assets: Res<Assets<Image>>,
+    layouts: Res<Assets<TextureAtlasLayout>>,
     mut commands: Commands
 ) -> Result
 where
@@ -338,8 +398,9 @@ where
 // TODO: kdtree or quadtree?
     // collect bounding boxes for sprites
     let bboxes = sprite_collision_query.iter()
-        .map(|(e, gt, a, s, n)| {
-            let image_size = assets.get(&s.image).unwrap().size_f32();
+        .filter_map(|(e, gt, a, s, is_map)| {
+            // an image still loading or which failed to load cannot be hit
+            let image_size = sprite_size(s, &assets, &layouts)?;
             let scaled = image_size * gt.scale().truncate();
             let pos = gt.translation();
             let mut bbox = Rect::from_center_size(pos.truncate(), scaled);
@@ -360,9 +421,13 @@ where
             bbox.min += ashift;
             bbox.max += ashift;
 
-            let b = a_query.bottom(e);
-
-            (e, pos.z, b, bbox)
+            // maps are not stacked on, only placed on
+            Some(if is_map {
+                (e, pos.z, e, bbox, DropTargetType::Map)
+            }
+            else {
+                (e, pos.z, a_query.bottom(e), bbox, DropTargetType::Piece)
+            })
         })
         .collect::<Vec<_>>();
 
@@ -389,6 +454,8 @@ where
             &bboxes,
             &mut ray_cast,
             &mrcs,
+            &container_query,
+            &a_query,
             root,
             gt_query,
             sg_query
@@ -415,6 +482,8 @@ where
             &bboxes,
             &mut ray_cast,
             &mrcs,
+            &container_query,
+            &a_query,
             root,
             gt_query,
             sg_query
@@ -4,14 +4,17 @@ use bevy::ecs::{
 };
 
 use crate::{
+    container,
     grid,
     log::{RedoGroupEvent, UndoGroupEvent},
+    map,
     piece::{
         clone::{RedoCloneEvent, UndoCloneEvent},
         create::{RedoCreateEvent, UndoCreateEvent},
         delete::{RedoDeleteEvent, UndoDeleteEvent},
         flip::{RedoFlipEvent, UndoFlipEvent},
         r#move::{RedoMoveEvent, UndoMoveEvent},
+        property::{RedoSetPropertyEvent, UndoSetPropertyEvent},
         rotate::{RedoRotateEvent, UndoRotateEvent},
         splice::{RedoSpliceEvent, UndoSpliceEvent}
     },
@@ -22,6 +25,8 @@ use crate::{
 pub enum EditType {
     CreateSurface,
     CreateGrid,
+    CreateMap,
+    CreateContainer,
     Clone,
     Create,
     Delete,
@@ -29,6 +34,7 @@ pub enum EditType {
     Group,
     Move,
     Rotate,
+    SetProperty,
     Splice
 }
 
@@ -37,6 +43,8 @@ impl EditType {
         match self {
             EditType::CreateSurface => commands.trigger(surface::create::UndoCreateEvent { entity }),
             EditType::CreateGrid => commands.trigger(grid::create::UndoCreateEvent { entity }),
+            EditType::CreateMap => commands.trigger(map::create::UndoCreateEvent { entity }),
+            EditType::CreateContainer => commands.trigger(container::create::UndoCreateEvent { entity }),
             EditType::Clone => commands.trigger(UndoCloneEvent { entity }),
             EditType::Create => commands.trigger(UndoCreateEvent { entity }),
             EditType::Delete => commands.trigger(UndoDeleteEvent { entity }),
@@ -44,6 +52,7 @@ impl EditType {
             EditType::Group => commands.trigger(UndoGroupEvent { entity }),
             EditType::Move => commands.trigger(UndoMoveEvent { entity }),
             EditType::Rotate => commands.trigger(UndoRotateEvent { entity }),
+            EditType::SetProperty => commands.trigger(UndoSetPropertyEvent { entity }),
             EditType::Splice => commands.trigger(UndoSpliceEvent { entity })
         }
     }
@@ -52,6 +61,8 @@ impl EditType {
         match self {
             EditType::CreateSurface => commands.trigger(surface::create::RedoCreateEvent { entity }),
             EditType::CreateGrid => commands.trigger(grid::create::RedoCreateEvent { entity }),
+            EditType::CreateMap => commands.trigger(map::create::RedoCreateEvent { entity }),
+            EditType::CreateContainer => commands.trigger(container::create::RedoCreateEvent { entity }),
             EditType::Clone => commands.trigger(RedoCloneEvent { entity }),
             EditType::Create => commands.trigger(RedoCreateEvent { entity }),
             EditType::Delete => commands.trigger(RedoDeleteEvent { entity }),
@@ -59,6 +70,7 @@ impl EditType {
             EditType::Group => commands.trigger(RedoGroupEvent { entity }),
             EditType::Move => commands.trigger(RedoMoveEvent { entity }),
             EditType::Rotate => commands.trigger(RedoRotateEvent { entity }),
+            EditType::SetProperty => commands.trigger(RedoSetPropertyEvent { entity }),
             EditType::Splice => commands.trigger(RedoSpliceEvent { entity })
         }
     }
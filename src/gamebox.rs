@@ -1,16 +1,26 @@
 use bevy::{
+    color::Srgba,
     ecs::prelude::Resource,
     math::Vec2
 };
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    mem,
+    path::{Path, PathBuf}
+};
 
 use crate::{
     actionfunc::ActionFunc,
-    keys::KeyBinding
+    keys::KeyBinding,
+    loader::DEFAULT_DPI
 };
 
+pub mod template;
+
 #[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum Anchor {
@@ -63,7 +73,8 @@ const fn default_scale() -> f32 {
     1.0
 }
 
-/*
+// maps, grids and containers laid out together; their positions are
+// relative to the group, which moves, turns and scales them as one
 #[derive(Debug, Deserialize)]
 pub struct GroupDefinition {
     #[serde(default)]
@@ -76,13 +87,25 @@ pub struct GroupDefinition {
     pub s: f32,
     #[serde(default)]
     pub a: f32,
-    #[serde(default)]
-    pub anchor: Anchor,
     pub children: Vec<SurfaceItem>
 }
 
+impl GroupDefinition {
+    // moves an item from the group's frame to its parent's
+    fn place(&self, x: &mut f32, y: &mut f32, s: &mut f32, a: &mut f32) {
+        let (sin, cos) = self.a.to_radians().sin_cos();
+        let (gx, gy) = (*x * self.s, *y * self.s);
+
+        *x = self.x + gx * cos - gy * sin;
+        *y = self.y + gx * sin + gy * cos;
+        *s *= self.s;
+        *a += self.a;
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct MapDefinition {
+    pub id: u32,
     #[serde(default)]
     pub name: String,
     #[serde(default)]
@@ -97,7 +120,6 @@ pub struct MapDefinition {
     pub anchor: Anchor,
     pub image: String
 }
-*/
 
 // TODO
 // first hex column: high or low?
@@ -164,12 +186,118 @@ pub enum GridDefinition {
     Hex(HexGridDefinition)
 }
 
+impl GridDefinition {
+    // cells take the object ids following the grid's own
+    pub fn cell_count(&self) -> u32 {
+        match self {
+            GridDefinition::Hex(h) => h.cols * h.rows,
+            GridDefinition::Rect(r) => r.cols * r.rows
+        }
+    }
+}
+
+// how a container arranges the pieces in it
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all(deserialize = "lowercase"))]
+pub enum Layout {
+    #[default]
+    Row,
+    Column,
+    Grid,
+    Pile
+}
+
+const fn default_spacing() -> f32 {
+    60.0
+}
+
+const fn default_cols() -> u32 {
+    1
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContainerDefinition {
+    pub id: u32,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub x: f32,
+    #[serde(default)]
+    pub y: f32,
+    #[serde(default = "default_scale")]
+    pub s: f32,
+    #[serde(default)]
+    pub a: f32,
+    #[serde(default)]
+    pub anchor: Anchor,
+    pub w: f32,
+    pub h: f32,
+    #[serde(default)]
+    pub layout: Layout,
+    // the distance between neighbouring pieces
+    #[serde(default = "default_spacing")]
+    pub spacing: f32,
+    // the width of a grid layout, in pieces
+    #[serde(default = "default_cols")]
+    pub cols: u32
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(untagged)]
 pub enum SurfaceItem {
-//    Map(MapDefinition),
+    Map(MapDefinition),
     Grid(GridDefinition),
-//    Group(GroupDefinition)
+    Container(ContainerDefinition),
+    Group(GroupDefinition)
+}
+
+// a grid placed at the start, either on the surface or on a map
+#[derive(Debug, Deserialize)]
+pub struct SetupGrid {
+    pub grid: u32,
+    pub map: Option<u32>
+}
+
+// a piece placed at the start; x, y are relative to the map if it is on
+// one, and ignored if it is in a grid cell or a container
+#[derive(Debug, Deserialize)]
+pub struct SetupPiece {
+    pub piece: u32,
+    #[serde(default)]
+    pub x: f32,
+    #[serde(default)]
+    pub y: f32,
+    pub map: Option<u32>,
+    pub grid: Option<u32>,
+    pub cell: Option<[u32; 2]>,
+    pub container: Option<u32>,
+    #[serde(default)]
+    pub face: usize,
+    #[serde(default)]
+    pub a: f32,
+    #[serde(default)]
+    pub anchor: Anchor
+}
+
+// the initial layout of the surface, used when there is no log
+#[derive(Debug, Default, Deserialize)]
+pub struct SurfaceDefinition {
+    #[serde(default)]
+    pub map: Vec<u32>,
+    #[serde(default)]
+    pub grid: Vec<SetupGrid>,
+    #[serde(default)]
+    pub container: Vec<u32>,
+    #[serde(default)]
+    pub piece: Vec<SetupPiece>
+}
+
+// a named starting position, laid out like the surface
+#[derive(Debug, Deserialize)]
+pub struct ScenarioDefinition {
+    pub name: String,
+    #[serde(flatten)]
+    pub surface: SurfaceDefinition
 }
 
 #[derive(Debug, Deserialize)]
@@ -195,9 +323,19 @@ pub enum ImageDefinition {
         cgap: i32,
         #[serde(default)]
         rgap: i32
+    },
+    // a vector image, rasterized at the given resolution
+    Vector {
+        file: String,
+        #[serde(default = "default_dpi")]
+        dpi: f32
     }
 }
 
+const fn default_dpi() -> f32 {
+    DEFAULT_DPI
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct Action {
     pub label: String,
@@ -205,18 +343,176 @@ pub struct Action {
     pub key: Option<KeyBinding>
 }
 
+// a piece property; untagged, so integers are tried before floats
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum PropertyValue {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String)
+}
+
+impl PropertyValue {
+    // whether the value counts as set for a layer condition
+    pub fn is_set(&self) -> bool {
+        match self {
+            PropertyValue::Bool(b) => *b,
+            PropertyValue::Int(i) => *i != 0,
+            PropertyValue::Float(x) => *x != 0.0,
+            PropertyValue::Text(s) => !s.is_empty()
+        }
+    }
+}
+
+impl fmt::Display for PropertyValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PropertyValue::Bool(b) => write!(f, "{b}"),
+            PropertyValue::Int(i) => write!(f, "{i}"),
+            PropertyValue::Float(x) => write!(f, "{x}"),
+            PropertyValue::Text(s) => write!(f, "{s}")
+        }
+    }
+}
+
+// a property which must be set for a layer to show, or with ! must not be
+#[derive(Clone, Debug, Deserialize)]
+#[serde(from = "String")]
+pub struct Condition {
+    pub key: String,
+    pub negate: bool
+}
+
+impl From<String> for Condition {
+    fn from(s: String) -> Self {
+        match s.strip_prefix('!') {
+            Some(key) => Condition { key: key.into(), negate: true },
+            None => Condition { key: s, negate: false }
+        }
+    }
+}
+
+impl Condition {
+    pub fn holds(&self, props: &HashMap<String, PropertyValue>) -> bool {
+        props.get(&self.key).is_some_and(PropertyValue::is_set) != self.negate
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct LayerDefinition {
+    pub image: String,
+    #[serde(default)]
+    pub x: f32,
+    #[serde(default)]
+    pub y: f32,
+    pub tint: Option<String>,
+    pub when: Option<Condition>
+}
+
+impl LayerDefinition {
+    pub fn is_plain(&self) -> bool {
+        self.x == 0.0 && self.y == 0.0 && self.tint.is_none() && self.when.is_none()
+    }
+}
+
+// a face is one image, or a stack of layers drawn bottom first
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum FaceDefinition {
+    Image(String),
+    Layers(Vec<LayerDefinition>)
+}
+
+impl FaceDefinition {
+    pub fn images(&self) -> impl Iterator<Item = &str> {
+        let (image, layers) = match self {
+            FaceDefinition::Image(image) => (Some(image.as_str()), &[][..]),
+            FaceDefinition::Layers(layers) => (None, &layers[..])
+        };
+
+        image.into_iter().chain(layers.iter().map(|l| l.image.as_str()))
+    }
+}
+
 // TODO: should faces have ids in addition to names?
 
 const fn default_true() -> bool {
     true
 }
 
+const fn default_label_size() -> f32 {
+    16.0
+}
+
+fn default_label_color() -> String {
+    "#000000".into()
+}
+
+// text drawn over a piece, placed relative to the piece's origin
+#[derive(Clone, Debug, Deserialize)]
+pub struct LabelDefinition {
+    pub text: String,
+    #[serde(default)]
+    pub x: f32,
+    #[serde(default)]
+    pub y: f32,
+    pub font: Option<String>,
+    #[serde(default = "default_label_size")]
+    pub size: f32,
+    #[serde(default = "default_label_color")]
+    pub color: String,
+    // the faces on which the label shows; empty is all of them
+    #[serde(default)]
+    pub faces: Vec<usize>
+}
+
+impl LabelDefinition {
+    // the properties named by {key} in the text
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.text.split('{')
+            .skip(1)
+            .filter_map(|s| s.split_once('}'))
+            .map(|(k, _)| k)
+    }
+
+    // the text with each {key} replaced by the value of that property
+    pub fn format(&self, props: &HashMap<String, PropertyValue>) -> String {
+        let mut out = String::new();
+        let mut rest = self.text.as_str();
+
+        while let Some((pre, post)) = rest.split_once('{') {
+            out.push_str(pre);
+            match post.split_once('}') {
+                Some((key, post)) => {
+                    match props.get(key) {
+                        Some(v) => out.push_str(&v.to_string()),
+                        None => out.push_str(&format!("{{{key}}}"))
+                    }
+                    rest = post;
+                },
+                None => {
+                    out.push('{');
+                    rest = post;
+                }
+            }
+        }
+
+        out.push_str(rest);
+        out
+    }
+
+    pub fn shown_on(&self, face: usize) -> bool {
+        self.faces.is_empty() || self.faces.contains(&face)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct PieceType {
     pub id: u32,
     pub name: String,
     #[serde(default)]
-    pub faces: Vec<String>,
+    pub faces: Vec<FaceDefinition>,
     #[serde(default)]
     pub actions: Vec<Action>,
     #[serde(default = "default_true")]
@@ -224,18 +520,31 @@ pub struct PieceType {
     #[serde(default = "default_true")]
     pub draggable: bool,
     #[serde(default)]
-    pub stacking_group: u32
+    pub stacking_group: u32,
+    #[serde(default)]
+    pub properties: HashMap<String, PropertyValue>,
+    #[serde(default)]
+    pub labels: Vec<LabelDefinition>
 }
 
+// a gamebox as written, before it has been checked
 #[derive(Debug, Deserialize)]
-struct MaybeGameBox {
+pub struct MaybeGameBox {
     #[serde(default)]
     pub images: HashMap<String, ImageDefinition>,
     #[serde(default)]
     pub grid: Vec<GridDefinition>,
     #[serde(default)]
+    pub map: Vec<MapDefinition>,
+    #[serde(default)]
+    pub container: Vec<ContainerDefinition>,
+    #[serde(default)]
+    pub group: Vec<GroupDefinition>,
+    #[serde(default)]
     pub piece: Vec<PieceType>,
-//    pub surface: SurfaceItem
+    pub surface: Option<SurfaceDefinition>,
+    #[serde(default)]
+    pub scenario: Vec<ScenarioDefinition>
 }
 
 // TODO: rename fields? pieces is probably a nicer name?
@@ -244,63 +553,733 @@ struct MaybeGameBox {
 pub struct GameBox {
     pub images: HashMap<String, ImageDefinition>,
     pub grid: HashMap<u32, GridDefinition>,
+    pub map: HashMap<u32, MapDefinition>,
+    pub container: HashMap<u32, ContainerDefinition>,
     pub piece: HashMap<u32, PieceType>,
-//    pub surface: SurfaceItem
+    pub surface: Option<SurfaceDefinition>,
+    pub scenario: Vec<ScenarioDefinition>
+}
+
+// identifies the gamebox a log was made with
+#[derive(Clone, Debug, Deserialize, Resource, Serialize)]
+pub struct GameBoxInfo {
+    pub path: PathBuf,
+    pub name: String,
+    pub hash: String
+}
+
+impl GameBoxInfo {
+    pub fn new(path: &Path, contents: &str) -> Self {
+        let name = path.file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+
+        let hash = Sha256::digest(contents.as_bytes())
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+
+        GameBoxInfo {
+            path: path.to_owned(),
+            name,
+            hash
+        }
+    }
 }
 
 #[derive(Debug, thiserror::Error, Eq, PartialEq)]
-#[error("Malformed gamebox data")]
-pub struct GameBoxError;
+pub enum GameBoxDiagnostic {
+    #[error("piece type id {0} is used more than once")]
+    DuplicatePieceId(u32),
+    #[error("grid id {0} is used more than once")]
+    DuplicateGridId(u32),
+    #[error("map id {0} is used more than once")]
+    DuplicateMapId(u32),
+    #[error("container id {0} is used more than once")]
+    DuplicateContainerId(u32),
+    #[error("container {0} has a grid layout with no columns")]
+    NoContainerColumns(u32),
+    #[error("map {map}: image {image:?} names no image or grid cell")]
+    UnknownMapImage {
+        map: u32,
+        image: String
+    },
+    #[error("piece type {piece} has no faces")]
+    NoFaces {
+        piece: u32
+    },
+    #[error("piece type {piece}: face {face:?} names no image")]
+    UnknownFaceImage {
+        piece: u32,
+        face: String
+    },
+    #[error("piece type {piece}: face {face:?} must be a grid cell, as image@col,row")]
+    MalformedGridKey {
+        piece: u32,
+        face: String
+    },
+    #[error("piece type {piece}: face {face:?} is outside the {cols}x{rows} grid image {image:?}")]
+    GridKeyOutOfRange {
+        piece: u32,
+        face: String,
+        image: String,
+        cols: u32,
+        rows: u32
+    },
+    #[error("piece type {piece}: face {face:?} picks a cell of {image:?}, which is not a grid image")]
+    NotAGridImage {
+        piece: u32,
+        face: String,
+        image: String
+    },
+    #[error("piece type {piece}: action {label:?} flips, but the piece has fewer than two faces")]
+    FlipWithoutFaces {
+        piece: u32,
+        label: String
+    },
+    #[error("piece type {piece}: action {label:?} adjusts {key:?}, which is not an integer property of the piece")]
+    AdjustWithoutProperty {
+        piece: u32,
+        label: String,
+        key: String
+    },
+    #[error("piece type {piece}: action {label:?} toggles {key:?}, which is not a boolean property of the piece")]
+    ToggleWithoutProperty {
+        piece: u32,
+        label: String,
+        key: String
+    },
+    #[error("piece type {piece}: face {face} has no layers")]
+    NoLayers {
+        piece: u32,
+        face: usize
+    },
+    #[error("piece type {piece}: a layer of face {face} has tint {tint:?}, which is not a hex color")]
+    BadLayerTint {
+        piece: u32,
+        face: usize,
+        tint: String
+    },
+    #[error("piece type {piece}: a layer of face {face} depends on property {key:?}, which the piece does not have")]
+    LayerUnknownProperty {
+        piece: u32,
+        face: usize,
+        key: String
+    },
+    #[error("piece type {piece}: label {label} shows property {key:?}, which the piece does not have")]
+    LabelUnknownProperty {
+        piece: u32,
+        label: usize,
+        key: String
+    },
+    #[error("piece type {piece}: label {label} has color {color:?}, which is not a hex color")]
+    BadLabelColor {
+        piece: u32,
+        label: usize,
+        color: String
+    },
+    #[error("piece type {piece}: label {label} shows on face {face}, which does not exist")]
+    LabelUnknownFace {
+        piece: u32,
+        label: usize,
+        face: usize
+    },
+    #[error("surface: map {0} does not exist")]
+    SetupUnknownMap(u32),
+    #[error("surface: grid {0} does not exist")]
+    SetupUnknownGrid(u32),
+    #[error("surface: piece type {0} does not exist")]
+    SetupUnknownPiece(u32),
+    #[error("surface: map {0} is placed more than once")]
+    SetupDuplicateMap(u32),
+    #[error("surface: grid {0} is placed more than once")]
+    SetupDuplicateGrid(u32),
+    #[error("surface: map {0} is used but not placed")]
+    SetupUnplacedMap(u32),
+    #[error("surface: grid {0} is used but not placed")]
+    SetupUnplacedGrid(u32),
+    #[error("surface: container {0} does not exist")]
+    SetupUnknownContainer(u32),
+    #[error("surface: container {0} is placed more than once")]
+    SetupDuplicateContainer(u32),
+    #[error("surface: container {0} is used but not placed")]
+    SetupUnplacedContainer(u32),
+    #[error("surface: piece type {piece} is both in a container and elsewhere")]
+    SetupConflictingPlaces {
+        piece: u32
+    },
+    #[error("surface: piece type {piece} needs both a grid and a cell, and then no map")]
+    SetupMalformedCell {
+        piece: u32
+    },
+    #[error("surface: piece type {piece} is in a cell of grid {grid}, but only hex grid cells hold pieces")]
+    SetupNotAHexGrid {
+        piece: u32,
+        grid: u32
+    },
+    #[error("surface: piece type {piece} is in cell {col},{row}, outside the {cols}x{rows} grid {grid}")]
+    SetupCellOutOfRange {
+        piece: u32,
+        grid: u32,
+        col: u32,
+        row: u32,
+        cols: u32,
+        rows: u32
+    },
+    #[error("surface: piece type {piece} has no face {face}")]
+    SetupUnknownFace {
+        piece: u32,
+        face: usize
+    },
+    #[error("scenario {0:?} is defined more than once")]
+    DuplicateScenario(String),
+    #[error("scenario {scenario:?}: {diag}")]
+    InScenario {
+        scenario: String,
+        diag: Box<GameBoxDiagnostic>
+    },
+    #[error("image {image:?}: source {src:?} names no image or grid cell")]
+    UnknownImageSource {
+        image: String,
+        src: String
+    },
+    #[error("image {image:?}: {file:?} is AVIF, which this build cannot load without the avif feature")]
+    AvifUnsupported {
+        image: String,
+        file: String
+    },
+    #[error("image {image:?}: source chain loops back on itself")]
+    ImageSourceCycle {
+        image: String
+    }
+}
 
-// TODO: check that actions exist, etc
+#[derive(Debug, thiserror::Error, Eq, PartialEq)]
+#[error("Malformed gamebox data:{}", .0.iter().map(|d| format!("\n  {d}")).join(""))]
+pub struct GameBoxError(pub Vec<GameBoxDiagnostic>);
 
-impl TryFrom<MaybeGameBox> for GameBox {
-    type Error = GameBoxError;
+fn image_src(def: &ImageDefinition) -> Option<&String> {
+    match def {
+        ImageDefinition::Crop { src, .. } |
+        ImageDefinition::Grid { src, .. } => Some(src),
+        ImageDefinition::File(_) |
+        ImageDefinition::Vector { .. } => None
+    }
+}
 
-    fn try_from(m: MaybeGameBox) -> Result<Self, Self::Error> {
-// TODO: check that grid keys are formatted name@c,r
+fn parse_cell(cell: &str) -> Option<(u32, u32)> {
+    let (c, r) = cell.split_once(',')?;
+    Some((c.parse().ok()?, r.parse().ok()?))
+}
 
-        // check that face keys exist
-        if !m.piece.iter()
-            .flat_map(|p| &p.faces)
-            .unique()
-            .map(|f| f.rsplit_once('@').map(|(l, _)| l).unwrap_or(f))
-            .all(|f| m.images.contains_key(f))
+// splits a grid cell key, image@col,row
+pub fn split_cell_key(key: &str) -> Option<(&str, u32, u32)> {
+    let (image, cell) = key.rsplit_once('@')?;
+    let (c, r) = parse_cell(cell)?;
+    Some((image, c, r))
+}
+
+// the image a source refers to, either directly or by one of its grid cells
+fn source_key<'a>(
+    src: &'a str,
+    images: &HashMap<String, ImageDefinition>
+) -> Option<&'a str>
+{
+    if images.contains_key(src) {
+        return Some(src);
+    }
+
+    let (key, c, r) = split_cell_key(src)?;
+    match images.get(key)? {
+        ImageDefinition::Grid { cols, rows, .. } if c < *cols && r < *rows =>
+            Some(key),
+        _ => None
+    }
+}
+
+fn check_image_sources(
+    images: &HashMap<String, ImageDefinition>,
+    diags: &mut Vec<GameBoxDiagnostic>
+)
+{
+    for (k, v) in images.iter().sorted_by_key(|(k, _)| *k) {
+        // without a loader an AVIF image would never finish loading
+        if let ImageDefinition::File(f) = v
+            && !cfg!(feature = "avif")
+            && Path::new(f).extension().is_some_and(|e| e.eq_ignore_ascii_case("avif"))
         {
-            return Err(GameBoxError);
+            diags.push(GameBoxDiagnostic::AvifUnsupported {
+                image: k.clone(),
+                file: f.clone()
+            });
         }
 
-        // TODO: follow chains to reject loops
-        // check that crop source keys exist
-        if !m.images.iter()
-            .all(|(k, v)| match v {
-                ImageDefinition::Crop { src, .. } => m.images.contains_key(src),
-                ImageDefinition::Grid { src, .. } => m.images.contains_key(src),
-                _ => true
-            })
+        let Some(src) = image_src(v) else { continue; };
+
+        let Some(mut cur) = source_key(src, images) else {
+            diags.push(GameBoxDiagnostic::UnknownImageSource {
+                image: k.clone(),
+                src: src.clone()
+            });
+            continue;
+        };
+
+        // follow the chain until it reaches a file or revisits an image
+        let mut seen = HashSet::from([k.as_str()]);
+        while let Some(next) = images.get(cur)
+            .and_then(image_src)
+            .and_then(|s| source_key(s, images))
         {
-            return Err(GameBoxError);
+            if !seen.insert(cur) {
+                diags.push(GameBoxDiagnostic::ImageSourceCycle {
+                    image: k.clone()
+                });
+                break;
+            }
+            cur = next;
         }
+    }
+}
 
-        let mut piece = HashMap::new();
-        for p in m.piece {
-            // fail on duplicate piece type ids
-            piece.insert(p.id, p)
-                .map_or(Ok(()), |_| Err(GameBoxError))?;
+fn check_face(
+    piece: u32,
+    face: &str,
+    images: &HashMap<String, ImageDefinition>
+) -> Option<GameBoxDiagnostic>
+{
+    let (key, cell) = match images.get(face) {
+        Some(ImageDefinition::Grid { .. }) => {
+            return Some(GameBoxDiagnostic::MalformedGridKey {
+                piece,
+                face: face.into()
+            });
+        },
+        Some(_) => return None,
+        None => match face.rsplit_once('@') {
+            Some((key, cell)) => (key, cell),
+            None => return Some(GameBoxDiagnostic::UnknownFaceImage {
+                piece,
+                face: face.into()
+            })
         }
+    };
+
+    match images.get(key) {
+        None => Some(GameBoxDiagnostic::UnknownFaceImage {
+            piece,
+            face: face.into()
+        }),
+        Some(ImageDefinition::Grid { cols, rows, .. }) => {
+            let Some((c, r)) = parse_cell(cell) else {
+                return Some(GameBoxDiagnostic::MalformedGridKey {
+                    piece,
+                    face: face.into()
+                });
+            };
 
-        let grid = m.grid.into_iter()
-            .map(|gd| match gd {
-                GridDefinition::Rect(RectGridDefinition { id, .. }) |
-                GridDefinition::Hex(HexGridDefinition { id, .. }) => (id, gd)
+            (c >= *cols || r >= *rows).then(|| GameBoxDiagnostic::GridKeyOutOfRange {
+                piece,
+                face: face.into(),
+                image: key.into(),
+                cols: *cols,
+                rows: *rows
             })
-            .collect::<HashMap<_, _>>();
+        },
+        Some(_) => Some(GameBoxDiagnostic::NotAGridImage {
+            piece,
+            face: face.into(),
+            image: key.into()
+        })
+    }
+}
+
+fn check_piece(
+    p: &PieceType,
+    images: &HashMap<String, ImageDefinition>,
+    diags: &mut Vec<GameBoxDiagnostic>
+)
+{
+    if p.faces.is_empty() {
+        diags.push(GameBoxDiagnostic::NoFaces { piece: p.id });
+    }
+
+    diags.extend(
+        p.faces.iter()
+            .flat_map(FaceDefinition::images)
+            .unique()
+            .filter_map(|f| check_face(p.id, f, images))
+    );
+
+    for (i, f) in p.faces.iter().enumerate() {
+        let FaceDefinition::Layers(layers) = f else { continue; };
+
+        if layers.is_empty() {
+            diags.push(GameBoxDiagnostic::NoLayers { piece: p.id, face: i });
+        }
+
+        for l in layers {
+            if let Some(tint) = &l.tint
+                && Srgba::hex(tint).is_err()
+            {
+                diags.push(GameBoxDiagnostic::BadLayerTint {
+                    piece: p.id,
+                    face: i,
+                    tint: tint.clone()
+                });
+            }
+
+            if let Some(when) = &l.when
+                && !p.properties.contains_key(&when.key)
+            {
+                diags.push(GameBoxDiagnostic::LayerUnknownProperty {
+                    piece: p.id,
+                    face: i,
+                    key: when.key.clone()
+                });
+            }
+        }
+    }
+
+    // flipping needs another face to flip to
+    if p.faces.len() < 2 {
+        diags.extend(
+            p.actions.iter()
+                .filter(|a| matches!(a.action, ActionFunc::Flip(_)))
+                .map(|a| GameBoxDiagnostic::FlipWithoutFaces {
+                    piece: p.id,
+                    label: a.label.clone()
+                })
+        );
+    }
+
+    for (i, l) in p.labels.iter().enumerate() {
+        diags.extend(
+            l.keys()
+                .filter(|k| !p.properties.contains_key(*k))
+                .unique()
+                .map(|k| GameBoxDiagnostic::LabelUnknownProperty {
+                    piece: p.id,
+                    label: i,
+                    key: k.into()
+                })
+        );
+
+        if Srgba::hex(&l.color).is_err() {
+            diags.push(GameBoxDiagnostic::BadLabelColor {
+                piece: p.id,
+                label: i,
+                color: l.color.clone()
+            });
+        }
+
+        diags.extend(
+            l.faces.iter()
+                .filter(|f| **f >= p.faces.len())
+                .map(|f| GameBoxDiagnostic::LabelUnknownFace {
+                    piece: p.id,
+                    label: i,
+                    face: *f
+                })
+        );
+    }
+
+    // adjusting needs a number to adjust, and toggling a flag to toggle
+    diags.extend(
+        p.actions.iter()
+            .filter_map(|a| match &a.action {
+                ActionFunc::Adjust(key, _) if !matches!(p.properties.get(key), Some(PropertyValue::Int(_))) =>
+                    Some(GameBoxDiagnostic::AdjustWithoutProperty {
+                        piece: p.id,
+                        label: a.label.clone(),
+                        key: key.clone()
+                    }),
+                ActionFunc::Toggle(key) if !matches!(p.properties.get(key), Some(PropertyValue::Bool(_))) =>
+                    Some(GameBoxDiagnostic::ToggleWithoutProperty {
+                        piece: p.id,
+                        label: a.label.clone(),
+                        key: key.clone()
+                    }),
+                _ => None
+            })
+    );
+}
+
+fn check_setup_piece(
+    sp: &SetupPiece,
+    gamebox: &MaybeGameBox,
+    placed_maps: &HashSet<u32>,
+    placed_grids: &HashSet<u32>,
+    diags: &mut Vec<GameBoxDiagnostic>
+)
+{
+    match gamebox.piece.iter().find(|p| p.id == sp.piece) {
+        None => diags.push(GameBoxDiagnostic::SetupUnknownPiece(sp.piece)),
+        Some(p) if sp.face >= p.faces.len().max(1) => {
+            diags.push(GameBoxDiagnostic::SetupUnknownFace {
+                piece: sp.piece,
+                face: sp.face
+            });
+        },
+        Some(_) => {}
+    }
+
+    if let Some(m) = sp.map && !placed_maps.contains(&m) {
+        diags.push(GameBoxDiagnostic::SetupUnplacedMap(m));
+    }
+
+    match (sp.map, sp.grid, sp.cell) {
+        (_, None, None) => {},
+        (None, Some(g), Some([col, row])) => {
+            if !placed_grids.contains(&g) {
+                diags.push(GameBoxDiagnostic::SetupUnplacedGrid(g));
+            }
+
+            match gamebox.grid.iter().find(|gd| grid_id(gd) == g) {
+                Some(GridDefinition::Hex(h)) if col >= h.cols || row >= h.rows => {
+                    diags.push(GameBoxDiagnostic::SetupCellOutOfRange {
+                        piece: sp.piece,
+                        grid: g,
+                        col,
+                        row,
+                        cols: h.cols,
+                        rows: h.rows
+                    });
+                },
+                Some(GridDefinition::Rect(_)) => {
+                    diags.push(GameBoxDiagnostic::SetupNotAHexGrid {
+                        piece: sp.piece,
+                        grid: g
+                    });
+                },
+                _ => {}
+            }
+        },
+        _ => diags.push(GameBoxDiagnostic::SetupMalformedCell {
+            piece: sp.piece
+        })
+    }
+}
+
+fn check_setup(
+    surface: &SurfaceDefinition,
+    gamebox: &MaybeGameBox,
+    diags: &mut Vec<GameBoxDiagnostic>
+)
+{
+    diags.extend(
+        surface.map.iter()
+            .filter(|m| !gamebox.map.iter().any(|md| md.id == **m))
+            .map(|m| GameBoxDiagnostic::SetupUnknownMap(*m))
+    );
+
+    diags.extend(
+        surface.map.iter()
+            .duplicates()
+            .map(|m| GameBoxDiagnostic::SetupDuplicateMap(*m))
+    );
+
+    diags.extend(
+        surface.grid.iter()
+            .filter(|sg| !gamebox.grid.iter().any(|gd| grid_id(gd) == sg.grid))
+            .map(|sg| GameBoxDiagnostic::SetupUnknownGrid(sg.grid))
+    );
+
+    diags.extend(
+        surface.grid.iter()
+            .map(|sg| sg.grid)
+            .duplicates()
+            .map(GameBoxDiagnostic::SetupDuplicateGrid)
+    );
+
+    diags.extend(
+        surface.container.iter()
+            .filter(|c| !gamebox.container.iter().any(|cd| cd.id == **c))
+            .map(|c| GameBoxDiagnostic::SetupUnknownContainer(*c))
+    );
+
+    diags.extend(
+        surface.container.iter()
+            .duplicates()
+            .map(|c| GameBoxDiagnostic::SetupDuplicateContainer(*c))
+    );
+
+    let placed_maps = surface.map.iter().copied().collect::<HashSet<_>>();
+    let placed_grids = surface.grid.iter()
+        .map(|sg| sg.grid)
+        .collect::<HashSet<_>>();
+
+    diags.extend(
+        surface.grid.iter()
+            .filter_map(|sg| sg.map)
+            .filter(|m| !placed_maps.contains(m))
+            .map(GameBoxDiagnostic::SetupUnplacedMap)
+    );
+
+    let placed_containers = surface.container.iter()
+        .copied()
+        .collect::<HashSet<_>>();
+
+    for sp in &surface.piece {
+        check_setup_piece(sp, gamebox, &placed_maps, &placed_grids, diags);
+
+        if let Some(c) = sp.container {
+            if sp.map.is_some() || sp.grid.is_some() || sp.cell.is_some() {
+                diags.push(GameBoxDiagnostic::SetupConflictingPlaces {
+                    piece: sp.piece
+                });
+            }
+
+            if !placed_containers.contains(&c) {
+                diags.push(GameBoxDiagnostic::SetupUnplacedContainer(c));
+            }
+        }
+    }
+}
+
+fn grid_id(gd: &GridDefinition) -> u32 {
+    match gd {
+        GridDefinition::Rect(RectGridDefinition { id, .. }) |
+        GridDefinition::Hex(HexGridDefinition { id, .. }) => *id
+    }
+}
+
+// moves the items of a group, and of the groups in it, out to the top level
+fn flatten_group(mut g: GroupDefinition, gamebox: &mut MaybeGameBox) {
+    for item in mem::take(&mut g.children) {
+        match item {
+            SurfaceItem::Map(mut md) => {
+                g.place(&mut md.x, &mut md.y, &mut md.s, &mut md.a);
+                gamebox.map.push(md);
+            },
+            SurfaceItem::Grid(GridDefinition::Rect(mut r)) => {
+                g.place(&mut r.x, &mut r.y, &mut r.s, &mut r.a);
+                gamebox.grid.push(GridDefinition::Rect(r));
+            },
+            SurfaceItem::Grid(GridDefinition::Hex(mut h)) => {
+                g.place(&mut h.x, &mut h.y, &mut h.s, &mut h.a);
+                gamebox.grid.push(GridDefinition::Hex(h));
+            },
+            SurfaceItem::Container(mut cd) => {
+                g.place(&mut cd.x, &mut cd.y, &mut cd.s, &mut cd.a);
+                gamebox.container.push(cd);
+            },
+            SurfaceItem::Group(mut inner) => {
+                g.place(&mut inner.x, &mut inner.y, &mut inner.s, &mut inner.a);
+                flatten_group(inner, gamebox);
+            }
+        }
+    }
+}
+
+impl MaybeGameBox {
+    // groups only position what is in them, so once their items are moved
+    // out to the top level they have done their work
+    pub fn flatten_groups(&mut self) {
+        for g in mem::take(&mut self.group) {
+            flatten_group(g, self);
+        }
+    }
+
+    // every problem which would keep this from being a usable gamebox
+    pub fn diagnostics(&self) -> Vec<GameBoxDiagnostic> {
+        let mut diags = vec![];
+
+        check_image_sources(&self.images, &mut diags);
+
+        for p in &self.piece {
+            check_piece(p, &self.images, &mut diags);
+        }
+
+        diags.extend(
+            self.piece.iter()
+                .map(|p| p.id)
+                .duplicates()
+                .map(GameBoxDiagnostic::DuplicatePieceId)
+        );
+
+        diags.extend(
+            self.grid.iter()
+                .map(grid_id)
+                .duplicates()
+                .map(GameBoxDiagnostic::DuplicateGridId)
+        );
+
+        diags.extend(
+            self.map.iter()
+                .filter(|m| source_key(&m.image, &self.images).is_none())
+                .map(|m| GameBoxDiagnostic::UnknownMapImage {
+                    map: m.id,
+                    image: m.image.clone()
+                })
+        );
+
+        diags.extend(
+            self.map.iter()
+                .map(|m| m.id)
+                .duplicates()
+                .map(GameBoxDiagnostic::DuplicateMapId)
+        );
+
+        diags.extend(
+            self.container.iter()
+                .map(|c| c.id)
+                .duplicates()
+                .map(GameBoxDiagnostic::DuplicateContainerId)
+        );
+
+        diags.extend(
+            self.container.iter()
+                .filter(|c| matches!(c.layout, Layout::Grid) && c.cols == 0)
+                .map(|c| GameBoxDiagnostic::NoContainerColumns(c.id))
+        );
+
+        if let Some(surface) = &self.surface {
+            check_setup(surface, self, &mut diags);
+        }
+
+        diags.extend(
+            self.scenario.iter()
+                .map(|sc| &sc.name)
+                .duplicates()
+                .map(|name| GameBoxDiagnostic::DuplicateScenario(name.clone()))
+        );
+
+        for sc in &self.scenario {
+            let mut sdiags = vec![];
+            check_setup(&sc.surface, self, &mut sdiags);
+
+            diags.extend(
+                sdiags.into_iter()
+                    .map(|d| GameBoxDiagnostic::InScenario {
+                        scenario: sc.name.clone(),
+                        diag: Box::new(d)
+                    })
+            );
+        }
+
+        diags
+    }
+}
+
+impl TryFrom<MaybeGameBox> for GameBox {
+    type Error = GameBoxError;
+
+    fn try_from(mut m: MaybeGameBox) -> Result<Self, Self::Error> {
+        m.flatten_groups();
+
+        let diags = m.diagnostics();
+        if !diags.is_empty() {
+            return Err(GameBoxError(diags));
+        }
 
         Ok(GameBox {
             images: m.images,
-            grid,
-            piece,
-//            surface: m.surface
+            grid: m.grid.into_iter().map(|gd| (grid_id(&gd), gd)).collect(),
+            map: m.map.into_iter().map(|md| (md.id, md)).collect(),
+            container: m.container.into_iter().map(|cd| (cd.id, cd)).collect(),
+            piece: m.piece.into_iter().map(|p| (p.id, p)).collect(),
+            surface: m.surface,
+            scenario: m.scenario
         })
     }
 }
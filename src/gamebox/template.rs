@@ -0,0 +1,291 @@
+use serde::de::DeserializeOwned;
+use std::collections::HashSet;
+use toml::{Table, Value};
+
+use crate::{
+    config::merge,
+    gamebox::{ImageDefinition, PieceType}
+};
+
+// keys which drive a generator rather than being copied into its pieces
+const GENERATOR_KEYS: [&str; 5] = ["extends", "faces", "first_id", "count", "name"];
+
+#[derive(Debug, thiserror::Error)]
+pub enum TemplateError {
+    #[error("{0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("{what}: {source}")]
+    Piece {
+        what: String,
+        source: toml::de::Error
+    },
+    #[error("{0} must be a table")]
+    NotATable(String),
+    #[error("{0} must be a string")]
+    NotAString(String),
+    #[error("template {0:?} does not exist")]
+    UnknownTemplate(String),
+    #[error("template {0:?} is part of an extends cycle")]
+    Cycle(String),
+    #[error("generator {index}: face sheet {image:?} is not a grid image")]
+    NotAGrid {
+        index: usize,
+        image: String
+    },
+    #[error("generator {index}: face sheets are not all the same size")]
+    MismatchedSheets {
+        index: usize
+    },
+    #[error("generator {index}: no face sheets")]
+    NoSheets {
+        index: usize
+    },
+    #[error("generator {index}: first_id must be a piece id from 0 to {}, not {first_id}", u32::MAX)]
+    BadFirstId {
+        index: usize,
+        first_id: String
+    }
+}
+
+fn as_table(v: Value, what: &str) -> Result<Table, TemplateError> {
+    match v {
+        Value::Table(t) => Ok(t),
+        _ => Err(TemplateError::NotATable(what.into()))
+    }
+}
+
+fn take_str(
+    t: &mut Table,
+    key: &str,
+    what: &str
+) -> Result<Option<String>, TemplateError>
+{
+    match t.remove(key) {
+        None => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s)),
+        Some(_) => Err(TemplateError::NotAString(format!("{what}.{key}")))
+    }
+}
+
+// the fields of a template, with those of the templates it extends beneath
+fn resolve(
+    name: &str,
+    templates: &Table,
+    seen: &mut HashSet<String>
+) -> Result<Table, TemplateError>
+{
+    if !seen.insert(name.into()) {
+        return Err(TemplateError::Cycle(name.into()));
+    }
+
+    let mut fields = templates.get(name)
+        .cloned()
+        .ok_or_else(|| TemplateError::UnknownTemplate(name.into()))
+        .and_then(|v| as_table(v, &format!("template.{name}")))?;
+
+    let Some(base) = take_str(&mut fields, "extends", &format!("template.{name}"))? else {
+        return Ok(fields);
+    };
+
+    let mut merged = resolve(&base, templates, seen)?;
+    merge(&mut merged, fields);
+    Ok(merged)
+}
+
+// lays a piece's own fields over those of the template it extends
+fn extend(
+    mut fields: Table,
+    templates: &Table,
+    what: &str
+) -> Result<Table, TemplateError>
+{
+    let Some(base) = take_str(&mut fields, "extends", what)? else {
+        return Ok(fields);
+    };
+
+    let mut merged = resolve(&base, templates, &mut HashSet::new())?;
+    merge(&mut merged, fields);
+    Ok(merged)
+}
+
+// the columns and rows of a grid image
+fn sheet_size(
+    index: usize,
+    image: &str,
+    images: &Table
+) -> Result<(u32, u32), TemplateError>
+{
+    let not_a_grid = || TemplateError::NotAGrid {
+        index,
+        image: image.into()
+    };
+
+    let def = images.get(image).cloned().ok_or_else(not_a_grid)?;
+
+    match def.try_into::<ImageDefinition>() {
+        Ok(ImageDefinition::Grid { cols, rows, .. }) => Ok((cols, rows)),
+        _ => Err(not_a_grid())
+    }
+}
+
+// expands a generator into one piece per cell of its face sheets, each
+// with where it came from
+fn generate(
+    index: usize,
+    gen_table: Table,
+    templates: &Table,
+    images: &Table,
+    next_id: &mut i64
+) -> Result<Vec<(Value, String)>, TemplateError>
+{
+    let what = format!("generate[{index}]");
+    let mut fields = extend(gen_table, templates, &what)?;
+
+    let sheets = match fields.get("faces") {
+        Some(Value::Array(a)) => a.iter()
+            .map(|v| v.as_str()
+                .map(str::to_owned)
+                .ok_or_else(|| TemplateError::NotAString(format!("{what}.faces")))
+            )
+            .collect::<Result<Vec<_>, _>>()?,
+        _ => vec![]
+    };
+
+    let Some(first) = sheets.first() else {
+        return Err(TemplateError::NoSheets { index });
+    };
+
+    let (cols, rows) = sheet_size(index, first, images)?;
+    for s in &sheets[1..] {
+        if sheet_size(index, s, images)? != (cols, rows) {
+            return Err(TemplateError::MismatchedSheets { index });
+        }
+    }
+
+    match fields.get("first_id") {
+        None => {},
+        Some(Value::Integer(id)) if u32::try_from(*id).is_ok() => *next_id = *id,
+        Some(v) => return Err(TemplateError::BadFirstId {
+            index,
+            first_id: v.to_string()
+        })
+    }
+
+    let count = match fields.get("count") {
+        Some(Value::Integer(n)) => (*n).max(0) as u32,
+        _ => cols * rows
+    };
+
+    let name = take_str(&mut fields, "name", &what)?;
+
+    for k in GENERATOR_KEYS {
+        fields.remove(k);
+    }
+
+    // cells are taken a row at a time
+    let pieces = (0..rows)
+        .flat_map(|r| (0..cols).map(move |c| (c, r)))
+        .take(count as usize)
+        .map(|(c, r)| {
+            let mut piece = fields.clone();
+
+            let faces = sheets.iter()
+                .map(|s| Value::String(format!("{s}@{c},{r}")))
+                .collect::<Vec<_>>();
+
+            let piece_name = match &name {
+                Some(n) => n.replace("{col}", &c.to_string())
+                    .replace("{row}", &r.to_string()),
+                None => format!("{first}@{c},{r}")
+            };
+
+            piece.insert("id".into(), Value::Integer(*next_id));
+            piece.insert("name".into(), Value::String(piece_name));
+            piece.insert("faces".into(), Value::Array(faces));
+            *next_id += 1;
+
+            (Value::Table(piece), format!("{what} cell {c},{r}"))
+        })
+        .collect();
+
+    Ok(pieces)
+}
+
+// replaces templates and generators with the piece types they describe;
+// the name of where each piece came from is returned alongside
+pub fn expand(mut table: Table) -> Result<(Table, Vec<String>), TemplateError> {
+    let templates = match table.remove("template") {
+        Some(v) => as_table(v, "template")?,
+        None => Table::new()
+    };
+
+    let generators = match table.remove("generate") {
+        Some(Value::Array(a)) => a,
+        Some(_) => return Err(TemplateError::NotATable("generate".into())),
+        None => vec![]
+    };
+
+    let images = match table.get("images") {
+        Some(Value::Table(t)) => t.clone(),
+        _ => Table::new()
+    };
+
+    let mut pieces = match table.remove("piece") {
+        Some(Value::Array(a)) => a.into_iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let what = format!("piece[{i}]");
+                as_table(p, &what)
+                    .and_then(|p| extend(p, &templates, &what))
+                    .map(|p| (Value::Table(p), what))
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        Some(_) => return Err(TemplateError::NotATable("piece".into())),
+        None => vec![]
+    };
+
+    // generated ids follow the largest given one unless a generator says
+    let mut next_id = pieces.iter()
+        .filter_map(|(p, _)| p.get("id").and_then(Value::as_integer))
+        .max()
+        .map_or(0, |id| id + 1);
+
+    for (i, g) in generators.into_iter().enumerate() {
+        let g = as_table(g, &format!("generate[{i}]"))?;
+        pieces.extend(generate(i, g, &templates, &images, &mut next_id)?);
+    }
+
+    let (pieces, origins): (Vec<_>, Vec<_>) = pieces.into_iter().unzip();
+
+    table.insert("piece".into(), Value::Array(pieces));
+    Ok((table, origins))
+}
+
+// parses a gamebox, expanding its templates and generators
+pub fn from_str<T: DeserializeOwned>(s: &str) -> Result<T, TemplateError> {
+    let table = toml::from_str::<Table>(s)?;
+
+    // parsing the text itself keeps the line and column in errors
+    if !table.contains_key("template") && !table.contains_key("generate") {
+        return Ok(toml::from_str(s)?);
+    }
+
+    let (table, origins) = expand(table)?;
+
+    Value::Table(table.clone()).try_into().map_err(|e| {
+        // expanded pieces have no place in the text, so say which it was
+        let pieces = match table.get("piece") {
+            Some(Value::Array(a)) => a.as_slice(),
+            _ => &[]
+        };
+
+        pieces.iter()
+            .zip(origins)
+            .find_map(|(p, what)| p.clone()
+                .try_into::<PieceType>()
+                .err()
+                .map(|source| TemplateError::Piece { what, source })
+            )
+            .unwrap_or(TemplateError::Toml(e))
+    })
+}
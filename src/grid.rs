@@ -4,12 +4,13 @@ use bevy::{
         change_detection::{Res, ResMut},
         component::Component,
         entity::Entity,
+        event::Event,
         name::Name,
         observer::On,
-        prelude::{ChildOf, Commands, Query, With}
+        prelude::{ChildOf, Commands, Or, Query, With}
     },
     math::{
-        Vec2, Vec3,
+        Quat, Vec2, Vec3,
         prelude::{ConvexPolygon, Polyline2d, Rectangle}
     },
     mesh::{Mesh, Mesh2d},
@@ -17,20 +18,22 @@ use bevy::{
         Pickable,
         events::{Over, Out, Pointer}
     },
-    prelude::{Color, ColorMaterial, debug, EntityEvent, MeshMaterial2d, trace, Transform, Visibility}
+    prelude::{Color, ColorMaterial, debug, EntityEvent, MeshMaterial2d, trace, Transform, Visibility, warn}
 };
 use tracing::{enabled, instrument, Level};
 
 use crate::{
+    assets::GameBoxReloaded,
     drag::handle_drop,
-    gamebox::{Anchor, ColumnStagger, GridDefinition, HexGridDefinition, RectGridDefinition},
-    object::ObjectId
+    gamebox::{Anchor, ColumnStagger, GameBox, GridDefinition, HexGridDefinition, RectGridDefinition},
+    object::{ObjectId, ObjectIdMap},
+    piece::{Above, Piece}
 };
 
 pub mod create;
 
 #[derive(Clone, Component, Copy, Debug, Default)]
-struct RectGrid;
+pub struct RectGrid;
 
 #[derive(Clone, Component, Copy, Debug, Default)]
 pub struct RectGridCell;
@@ -54,7 +57,7 @@ fn spawn_rect_grid(
     commands: &mut Commands
 )
 {
-    let RectGridDefinition { x, y, anchor, cols, rows, cw, rh, .. } = def;
+    let RectGridDefinition { id, name, x, y, anchor, cols, rows, cw, rh, .. } = def;
 
     let grect = Rectangle::new(*cols as f32 * cw, *rows as f32 * rh);
 
@@ -99,13 +102,25 @@ fn spawn_rect_grid(
     let grid_material = materials.add(grid_color);
     let unhighlight_material = materials.add(unhighlight_color);
 
+    // the grid container; its children are placed relative to the parent
+    let gid = commands.spawn((
+        RectGrid,
+        ObjectId(oid),
+        GridTypeId(*id),
+        Name::from(name.as_ref()),
+        ChildOf(parent),
+        Transform::IDENTITY,
+        Pickable::IGNORE,
+        Visibility::Inherited
+    )).id();
+
     let gmesh = meshes.add(grect);
     commands.spawn((
         Mesh2d(gmesh),
         MeshMaterial2d(materials.add(Color::srgba(0.0, 1.0, 0.0, 0.2))),
         Pickable::default(),
         tg,
-        ChildOf(parent)
+        ChildOf(gid)
     ));
 
     for r in 0..*rows {
@@ -119,7 +134,7 @@ fn spawn_rect_grid(
                     MeshMaterial2d(unhighlight_material.clone()),
                     Pickable::default(),
                     ct,
-                    ChildOf(parent)
+                    ChildOf(gid)
                 ))
                 .observe(recolor_cell_on::<Pointer<Over>>(highlight_color))
                 .observe(recolor_cell_on::<Pointer<Out>>(unhighlight_color));
@@ -130,13 +145,13 @@ fn spawn_rect_grid(
                     MeshMaterial2d(grid_material.clone()),
                     Pickable::default(),
                     ct,
-                    ChildOf(parent)
+                    ChildOf(gid)
                 ));
         }
     }
 }
 
-fn anchor_to_vec3(rect: Rectangle, anchor: Anchor) -> Vec3 {
+pub fn anchor_to_vec3(rect: Rectangle, anchor: Anchor) -> Vec3 {
     match anchor {
         Anchor::BottomLeft => rect.half_size,
         Anchor::BottomCenter => rect.half_size.with_x(0.0),
@@ -277,7 +292,7 @@ fn spawn_hex_grid(
             commands
                 .spawn((
                     HexGridCell,
-                    ObjectId(id + 1 + r * cols + c),
+                    ObjectId(oid + 1 + r * cols + c),
                     Mesh2d(cmesh.clone()),
                     MeshMaterial2d(unhighlight_material.clone()),
                     ChildOf(gid),
@@ -320,6 +335,120 @@ pub fn spawn_grid(
     }
 }
 
+pub fn grid_transform(g: &GridDefinition) -> Transform {
+    use std::f32::consts::PI;
+
+    match g {
+        GridDefinition::Hex(h) => {
+            Transform {
+                translation: Vec3::new(h.x, h.y, 1.0),
+                rotation: Quat::from_rotation_z(h.a * PI / 180.0),
+                scale: Vec3::new(h.s, h.s, 1.0)
+            }
+        },
+        GridDefinition::Rect(r) => {
+            // above the map the grid may be on
+            Transform {
+                translation: Vec3::new(r.x, r.y, 1.0),
+                rotation: Quat::from_rotation_z(r.a * PI / 180.0),
+                scale: Vec3::new(r.s, r.s, 1.0)
+            }
+        }
+    }
+}
+
+// pieces to put back on cells once their grid has been respawned
+#[derive(Event)]
+pub struct ReattachPieces(Vec<(Entity, u32, Entity)>);
+
+#[instrument(skip_all)]
+pub fn respawn_grids(
+    _evt: On<GameBoxReloaded>,
+    grid_query: Query<(Entity, &ObjectId, &GridTypeId, &ChildOf), Or<(With<HexGrid>, With<RectGrid>)>>,
+    cell_query: Query<(&ObjectId, &ChildOf), With<HexGridCell>>,
+    piece_query: Query<(Entity, &Above), With<Piece>>,
+    gamebox: Res<GameBox>,
+    objmap: Res<ObjectIdMap>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut commands: Commands
+)
+{
+    trace!("");
+
+    let mut reattach = vec![];
+
+    for (grid, oid, tid, parent) in grid_query {
+        let Some(gdef) = gamebox.grid.get(&tid.0) else {
+            warn!("grid type {} was removed; keeping grid {}", tid.0, oid.0);
+            continue;
+        };
+
+        // cells take the ids after the grid's; a grid cannot grow into
+        // ids already taken by other objects
+        if let GridDefinition::Hex(_) = gdef
+            && let Some(taken) = (oid.0 + 1..=oid.0 + gdef.cell_count())
+                .find(|id| objmap.0.get(id)
+                    .is_some_and(|e| !cell_query.get(*e)
+                        .is_ok_and(|(_, cp)| cp.0 == grid)
+                    )
+                )
+        {
+            warn!("grid {} cannot grow into object id {taken}; restart to resize it", oid.0);
+            continue;
+        }
+
+        // lift pieces off the cells so they survive the despawn
+        for (piece, above) in piece_query {
+            if let Ok((cell_oid, cell_parent)) = cell_query.get(above.0)
+                && cell_parent.0 == grid
+            {
+                commands.entity(piece)
+                    .remove::<Above>()
+                    .remove::<ChildOf>();
+
+                reattach.push((piece, cell_oid.0, parent.0));
+            }
+        }
+
+        commands.entity(grid).despawn();
+
+        spawn_grid(
+            oid.0,
+            gdef,
+            parent.0,
+            grid_transform(gdef),
+            &mut meshes,
+            &mut materials,
+            &mut commands
+        );
+    }
+
+    // this runs after the new cells are spawned
+    commands.trigger(ReattachPieces(reattach));
+}
+
+#[instrument(skip_all)]
+pub fn reattach_pieces(
+    evt: On<ReattachPieces>,
+    objmap: Res<ObjectIdMap>,
+    mut commands: Commands
+)
+{
+    trace!("");
+
+    for &(piece, cell_oid, fallback) in &evt.0 {
+        let parent = objmap.get(cell_oid).unwrap_or_else(|e| {
+            warn!("{e}; moving piece off the grid");
+            fallback
+        });
+
+        commands.entity(parent)
+            .add_one_related::<Above>(piece)
+            .add_child(piece);
+    }
+}
+
 fn recolor_cell_on<E: EntityEvent>(
     color: Color
 ) -> impl Fn(
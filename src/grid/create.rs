@@ -8,17 +8,16 @@ use bevy::{
         observer::On,
         prelude::{Commands, Entity, Query}
     },
-    math::{Quat, Vec3},
     mesh::Mesh,
-    prelude::{ColorMaterial, trace, Transform}
+    prelude::{ColorMaterial, trace}
 };
 use serde::{Deserialize, Serialize};
 use tracing::instrument;
 
 use crate::{
     edittype::EditType,
-    gamebox::{GameBox, GridDefinition},
-    grid::spawn_grid,
+    gamebox::GameBox,
+    grid::{grid_transform, spawn_grid},
     log::{EditIndex, Edits, handle_do},
     maxz::MaxZ,
     object::{NextObjectId, ObjectId, ObjectIdMap}
@@ -51,6 +50,7 @@ pub struct CreateEdit {
 #[instrument(skip_all)]
 pub fn on_create(
     evt: On<DoCreateEvent>,
+    gamebox: Res<GameBox>,
     mut next_object_id: ResMut<NextObjectId>,
     parent_query: Query<&ObjectId>,
     edit_query: Query<(Entity, &mut Edits, &mut EditIndex)>,
@@ -59,8 +59,12 @@ pub fn on_create(
 {
     trace!("");
 
+    let gdef = gamebox.grid.get(&evt.type_id)
+        .ok_or("unknown grid type")?;
+
+    // reserve ids for the cells, too
     let object_id = next_object_id.0;
-    next_object_id.0 += 1;
+    next_object_id.0 += 1 + gdef.cell_count();
 
     let parent_id = parent_query.get(evt.parent)?;
 
@@ -88,7 +92,7 @@ pub fn on_create_undo(
     // get the edit
     let Ok(cr) = edit.get(evt.entity) else { return Ok(()); };
     // get the entity being edited
-    let entity = *objmap.0.get(&cr.object_id).unwrap();
+    let entity = objmap.get(cr.object_id)?;
     // apply the change
     commands.entity(entity).despawn();
     Ok(())
@@ -109,36 +113,16 @@ pub fn on_create_redo(
     let Ok(cr) = edit.get(evt.entity) else { return Ok(()); };
 
     // get the parent
-    let parent = *objmap.0.get(&cr.parent_id).unwrap();
+    let parent = objmap.get(cr.parent_id)?;
 
     // apply the change
-
-    use std::f32::consts::PI;
-
     let gdef = &gamebox.grid[&cr.type_id];
 
-    let t = match gdef {
-        GridDefinition::Hex(h) => {
-            Transform {
-                translation: Vec3::new(h.x, h.y, 1.0),
-                rotation: Quat::from_rotation_z(h.a * PI / 180.0),
-                scale: Vec3::new(h.s, h.s, 1.0)
-            }
-        },
-        GridDefinition::Rect(r) => {
-            Transform {
-                translation: Vec3::new(r.x, r.y, 0.0),
-                rotation: Quat::from_rotation_z(r.a * PI / 180.0),
-                scale: Vec3::new(r.s, r.s, 1.0)
-            }
-        }
-    };
-
     spawn_grid(
         cr.object_id,
         gdef,
         parent,
-        t,
+        grid_transform(gdef),
         &mut meshes,
         &mut materials,
         &mut commands
@@ -0,0 +1,264 @@
+use itertools::Itertools;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    path::Path,
+    process::ExitCode
+};
+
+use crate::{
+    GameBoxPath, GameBoxPathError,
+    archive::{GameBoxFiles, read_gamebox},
+    gamebox::{FaceDefinition, ImageDefinition, MaybeGameBox, split_cell_key, template},
+    loader::{decode, RasterSettings, split_label}
+};
+
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Severity {
+    Error,
+    Warning
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning")
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String
+}
+
+impl Finding {
+    fn error(message: impl Into<String>) -> Self {
+        Finding { severity: Severity::Error, message: message.into() }
+    }
+
+    fn warning(message: impl Into<String>) -> Self {
+        Finding { severity: Severity::Warning, message: message.into() }
+    }
+}
+
+// open and fully decode each file image, noting its dimensions
+fn check_files(
+    images: &HashMap<String, ImageDefinition>,
+    files: &GameBoxFiles,
+    findings: &mut Vec<Finding>
+) -> HashMap<String, (u32, u32)>
+{
+    let mut dims = HashMap::new();
+
+    for (k, v) in images.iter().sorted_by_key(|(k, _)| *k) {
+        let (f, settings) = match v {
+            ImageDefinition::File(f) => (f, RasterSettings::default()),
+            ImageDefinition::Vector { file, dpi } => (file, RasterSettings { dpi: *dpi }),
+            _ => continue
+        };
+
+        let (f, label) = split_label(f);
+        let path = Path::new(f);
+
+        let bytes = match files.read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.is_not_found() => {
+                findings.push(Finding::error(format!(
+                    "image {k:?}: file {f:?} does not exist"
+                )));
+                continue;
+            },
+            Err(e) => {
+                findings.push(Finding::error(format!(
+                    "image {k:?}: cannot read {f:?}: {e}"
+                )));
+                continue;
+            }
+        };
+
+        match decode(bytes, path, label, &settings) {
+            Ok(img) => { dims.insert(k.clone(), (img.width(), img.height())); },
+            Err(e) => findings.push(Finding::error(format!(
+                "image {k:?}: cannot decode {f:?}: {e}"
+            )))
+        }
+    }
+
+    dims
+}
+
+// the extent of cells laid out with gaps between them
+fn span(start: u32, count: u32, size: u32, gap: i32) -> i64 {
+    start as i64 +
+        count as i64 * size as i64 +
+        (count as i64 - 1).max(0) * gap as i64
+}
+
+// the dimensions of an image or grid cell, where they are known
+fn source_size(
+    src: &str,
+    images: &HashMap<String, ImageDefinition>,
+    dims: &HashMap<String, (u32, u32)>
+) -> Option<(u32, u32)>
+{
+    match images.get(src) {
+        Some(ImageDefinition::File(_) | ImageDefinition::Vector { .. }) =>
+            dims.get(src).copied(),
+        Some(ImageDefinition::Crop { w, h, .. }) => Some((*w, *h)),
+        Some(ImageDefinition::Grid { cols, rows, cw, rh, cgap, rgap, .. }) => Some((
+            span(0, *cols, *cw, *cgap).try_into().ok()?,
+            span(0, *rows, *rh, *rgap).try_into().ok()?
+        )),
+        None => match images.get(split_cell_key(src)?.0)? {
+            ImageDefinition::Grid { cw, rh, .. } => Some((*cw, *rh)),
+            _ => None
+        }
+    }
+}
+
+fn check_regions(
+    images: &HashMap<String, ImageDefinition>,
+    dims: &HashMap<String, (u32, u32)>,
+    findings: &mut Vec<Finding>
+)
+{
+    for (k, v) in images.iter().sorted_by_key(|(k, _)| *k) {
+        let (src, right, bottom) = match v {
+            ImageDefinition::File(_) |
+            ImageDefinition::Vector { .. } => continue,
+            ImageDefinition::Crop { src, x, y, w, h } => (
+                src,
+                *x as i64 + *w as i64,
+                *y as i64 + *h as i64
+            ),
+            ImageDefinition::Grid { src, x, y, cols, rows, cw, rh, cgap, rgap } => (
+                src,
+                span(*x, *cols, *cw, *cgap),
+                span(*y, *rows, *rh, *rgap)
+            )
+        };
+
+        let Some((sw, sh)) = source_size(src, images, dims) else { continue; };
+
+        if right > sw as i64 || bottom > sh as i64 {
+            findings.push(Finding::error(format!(
+                "image {k:?}: region reaches ({right}, {bottom}), outside the {sw}x{sh} source {src:?}"
+            )));
+        }
+    }
+}
+
+fn check_fonts(
+    gamebox: &MaybeGameBox,
+    files: &GameBoxFiles,
+    findings: &mut Vec<Finding>
+)
+{
+    let fonts = gamebox.piece.iter()
+        .flat_map(|p| &p.labels)
+        .filter_map(|l| l.font.as_ref())
+        .unique()
+        .sorted();
+
+    for f in fonts {
+        match files.read(Path::new(f)) {
+            Ok(_) => {},
+            Err(e) if e.is_not_found() => findings.push(Finding::error(format!(
+                "font {f:?} does not exist"
+            ))),
+            Err(e) => findings.push(Finding::error(format!(
+                "cannot read font {f:?}: {e}"
+            )))
+        }
+    }
+}
+
+fn check_unused(gamebox: &MaybeGameBox, findings: &mut Vec<Finding>) {
+    let mut used = HashSet::new();
+
+    for v in gamebox.images.values() {
+        if let ImageDefinition::Crop { src, .. } |
+               ImageDefinition::Grid { src, .. } = v
+        {
+            used.insert(src.as_str());
+            if let Some((key, _, _)) = split_cell_key(src) {
+                used.insert(key);
+            }
+        }
+    }
+
+    for m in &gamebox.map {
+        used.insert(m.image.as_str());
+        if let Some((key, _, _)) = split_cell_key(&m.image) {
+            used.insert(key);
+        }
+    }
+
+    for f in gamebox.piece.iter()
+        .flat_map(|p| &p.faces)
+        .flat_map(FaceDefinition::images)
+    {
+        used.insert(f);
+        if let Some((key, _)) = f.rsplit_once('@') {
+            used.insert(key);
+        }
+    }
+
+    findings.extend(
+        gamebox.images.keys()
+            .filter(|k| !used.contains(k.as_str()))
+            .sorted()
+            .map(|k| Finding::warning(format!("image {k:?} is never used")))
+    );
+}
+
+pub fn lint(path: &Path, files: &GameBoxFiles) -> Vec<Finding> {
+    let gbs = match read_gamebox(path) {
+        Ok(s) => s,
+        Err(e) => return vec![
+            Finding::error(format!("cannot read {}: {e}", path.display()))
+        ]
+    };
+
+    // nothing else can be checked if the gamebox does not parse
+    let mut gamebox = match template::from_str::<MaybeGameBox>(&gbs) {
+        Ok(gb) => gb,
+        Err(e) => return vec![
+            Finding::error(format!("cannot parse {}: {e}", path.display()))
+        ]
+    };
+
+    gamebox.flatten_groups();
+
+    let mut findings = gamebox.diagnostics()
+        .into_iter()
+        .map(|d| Finding::error(d.to_string()))
+        .collect::<Vec<_>>();
+
+    let dims = check_files(&gamebox.images, files, &mut findings);
+    check_regions(&gamebox.images, &dims, &mut findings);
+    check_fonts(&gamebox, files, &mut findings);
+    check_unused(&gamebox, &mut findings);
+
+    findings
+}
+
+pub fn run_lint(gamebox_path: GameBoxPath) -> Result<ExitCode, GameBoxPathError> {
+    let files = GameBoxFiles::new(&gamebox_path)?;
+    let findings = lint(&gamebox_path.0, &files);
+
+    for f in findings.iter().sorted_by_key(|f| f.severity) {
+        println!("{}: {}", f.severity, f.message);
+    }
+
+    let errors = findings.iter()
+        .filter(|f| f.severity == Severity::Error)
+        .count();
+
+    println!("{errors} errors, {} warnings", findings.len() - errors);
+
+    Ok(if errors == 0 { ExitCode::SUCCESS } else { ExitCode::FAILURE })
+}
@@ -0,0 +1,108 @@
+use bevy::{
+    app::App,
+    asset::AssetApp
+};
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+use std::{
+    io,
+    path::Path
+};
+
+#[cfg(feature = "avif")]
+pub mod avif;
+pub mod pdf;
+pub mod svg;
+
+#[cfg(feature = "avif")]
+use crate::loader::avif::AvifLoader;
+use crate::loader::{
+    pdf::{PdfError, PdfLoader},
+    svg::{SvgError, SvgLoader}
+};
+
+// the resolution at which one vector unit is one pixel
+pub const DEFAULT_DPI: f32 = 96.0;
+
+// how vector images are turned into pixels
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct RasterSettings {
+    pub dpi: f32
+}
+
+impl Default for RasterSettings {
+    fn default() -> Self {
+        RasterSettings {
+            dpi: DEFAULT_DPI
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DecodeError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    Image(#[from] image::ImageError),
+    #[error("{0}")]
+    Svg(#[from] SvgError),
+    #[error("{0}")]
+    Pdf(#[from] PdfError)
+}
+
+pub fn is_pdf(path: &str) -> bool {
+    Path::new(path).extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("pdf"))
+}
+
+// splits an image path into the file and the page of a PDF, as
+// file.pdf#page=2; any other # is part of the file name
+pub fn split_label(path: &str) -> (&str, Option<&str>) {
+    match path.rsplit_once('#') {
+        Some((file, label)) if label.starts_with("page=") && is_pdf(file) =>
+            (file, Some(label)),
+        _ => (path, None)
+    }
+}
+
+// decode the contents of an image file as the asset loaders would, for use
+// where there is no asset server
+pub fn decode(
+    bytes: Vec<u8>,
+    path: &Path,
+    label: Option<&str>,
+    settings: &RasterSettings
+) -> Result<DynamicImage, DecodeError>
+{
+    let ext = path.extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase);
+
+    match ext.as_deref() {
+        Some("svg" | "svgz") => Ok(svg::rasterize(
+            &bytes,
+            settings.dpi,
+            svg::system_fonts()
+        )?),
+        Some("pdf") => {
+            let page = label.map_or(Ok(1), pdf::parse_page_label)?;
+            let pdfium = pdf::bind_pdfium()?;
+            Ok(pdf::render(&pdfium, bytes, page, settings.dpi)?)
+        },
+        _ => Ok(
+            image::ImageReader::new(io::Cursor::new(bytes))
+                .with_guessed_format()?
+                .decode()?
+        )
+    }
+}
+
+pub fn loader_plugin(app: &mut App) {
+    #[cfg(feature = "avif")]
+    app.init_asset_loader::<AvifLoader>();
+
+    app
+        .init_asset_loader::<SvgLoader>()
+        .init_asset_loader::<PdfLoader>();
+}
@@ -0,0 +1,45 @@
+use bevy::{
+    asset::{
+        AssetLoader, LoadContext, RenderAssetUsages,
+        io::Reader
+    },
+    image::Image,
+    reflect::TypePath
+};
+use image::ImageFormat;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AvifError {
+    #[error("cannot read AVIF: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("cannot decode AVIF: {0}")]
+    Decode(#[from] image::ImageError)
+}
+
+// bevy's own image loader does not decode AVIF
+#[derive(Default, TypePath)]
+pub struct AvifLoader;
+
+impl AssetLoader for AvifLoader {
+    type Asset = Image;
+    type Settings = ();
+    type Error = AvifError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>
+    ) -> Result<Image, AvifError>
+    {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes).await?;
+
+        let img = image::load_from_memory_with_format(&bytes, ImageFormat::Avif)?;
+        Ok(Image::from_dynamic(img, true, RenderAssetUsages::default()))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["avif"]
+    }
+}
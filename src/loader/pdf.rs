@@ -0,0 +1,108 @@
+use bevy::{
+    asset::{
+        AssetLoader, LoadContext, RenderAssetUsages,
+        io::Reader
+    },
+    image::Image,
+    reflect::TypePath
+};
+use image::DynamicImage;
+use pdfium_render::prelude::{Pdfium, PdfiumError, PdfRenderConfig};
+
+use crate::loader::RasterSettings;
+
+// PDF coordinates are in points
+const POINTS_PER_INCH: f32 = 72.0;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PdfError {
+    #[error("cannot read PDF: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("cannot load the PDFium library: {0}")]
+    Unavailable(String),
+    #[error("cannot render PDF: {0}")]
+    Render(#[from] PdfiumError),
+    #[error("PDF has no pages")]
+    NoPages,
+    #[error("PDF page label {0:?} is not page=N")]
+    BadLabel(String)
+}
+
+pub fn bind_pdfium() -> Result<Pdfium, PdfError> {
+    // prefer a library shipped alongside the program
+    Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path("./"))
+        .or_else(|_| Pdfium::bind_to_system_library())
+        .map(Pdfium::new)
+        .map_err(|e| PdfError::Unavailable(e.to_string()))
+}
+
+// pages are labeled page=N, counting from 1
+pub fn parse_page_label(label: &str) -> Result<u16, PdfError> {
+    label.strip_prefix("page=")
+        .and_then(|n| n.parse::<u16>().ok())
+        .filter(|n| *n > 0)
+        .ok_or_else(|| PdfError::BadLabel(label.into()))
+}
+
+// render one page of a PDF, counting from 1
+pub fn render(
+    pdfium: &Pdfium,
+    bytes: Vec<u8>,
+    page: u16,
+    dpi: f32
+) -> Result<DynamicImage, PdfError>
+{
+    let doc = pdfium.load_pdf_from_byte_vec(bytes, None)?;
+    let config = PdfRenderConfig::new()
+        .scale_page_by_factor(dpi / POINTS_PER_INCH);
+
+    if doc.pages().is_empty() {
+        return Err(PdfError::NoPages);
+    }
+
+    Ok(doc.pages().get(page - 1)?.render_with_config(&config)?.as_image())
+}
+
+#[derive(TypePath)]
+pub struct PdfLoader {
+    // the library might not be installed; that is an error only once
+    // someone tries to load a PDF
+    pdfium: Result<Pdfium, String>
+}
+
+impl Default for PdfLoader {
+    fn default() -> Self {
+        PdfLoader {
+            pdfium: bind_pdfium().map_err(|e| e.to_string())
+        }
+    }
+}
+
+impl AssetLoader for PdfLoader {
+    type Asset = Image;
+    type Settings = RasterSettings;
+    type Error = PdfError;
+
+    // only the first page is drawn; the pages of PDFs in a gamebox are
+    // drawn one at a time by the raster tasks, as they are asked for
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        settings: &RasterSettings,
+        _load_context: &mut LoadContext<'_>
+    ) -> Result<Image, PdfError>
+    {
+        let pdfium = self.pdfium.as_ref()
+            .map_err(|e| PdfError::Unavailable(e.clone()))?;
+
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes).await?;
+
+        let img = render(pdfium, bytes, 1, settings.dpi)?;
+        Ok(Image::from_dynamic(img, true, RenderAssetUsages::default()))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["pdf"]
+    }
+}
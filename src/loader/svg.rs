@@ -0,0 +1,103 @@
+use bevy::{
+    asset::{
+        AssetLoader, LoadContext, RenderAssetUsages,
+        io::Reader
+    },
+    image::Image,
+    reflect::TypePath
+};
+use image::{DynamicImage, RgbaImage};
+use resvg::{
+    tiny_skia::{Pixmap, Transform},
+    usvg::{self, fontdb::Database}
+};
+use std::sync::{Arc, LazyLock};
+
+use crate::loader::{DEFAULT_DPI, RasterSettings};
+
+#[derive(Debug, thiserror::Error)]
+pub enum SvgError {
+    #[error("cannot read SVG: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("cannot parse SVG: {0}")]
+    Parse(#[from] usvg::Error),
+    #[error("cannot rasterize SVG at {0} dpi")]
+    Size(f32)
+}
+
+// fonts for any text in the SVGs; finding them is slow, so it is done once,
+// and only once an SVG needs them
+static SYSTEM_FONTS: LazyLock<Arc<Database>> = LazyLock::new(|| {
+    let mut fontdb = Database::new();
+    fontdb.load_system_fonts();
+    Arc::new(fontdb)
+});
+
+pub fn system_fonts() -> Arc<Database> {
+    SYSTEM_FONTS.clone()
+}
+
+pub fn rasterize(
+    bytes: &[u8],
+    dpi: f32,
+    fontdb: Arc<Database>
+) -> Result<DynamicImage, SvgError>
+{
+    let options = usvg::Options {
+        fontdb,
+        ..Default::default()
+    };
+
+    let tree = usvg::Tree::from_data(bytes, &options)?;
+
+    let scale = dpi / DEFAULT_DPI;
+    let size = tree.size()
+        .to_int_size()
+        .scale_by(scale)
+        .ok_or(SvgError::Size(dpi))?;
+
+    let mut pixmap = Pixmap::new(size.width(), size.height())
+        .ok_or(SvgError::Size(dpi))?;
+
+    resvg::render(&tree, Transform::from_scale(scale, scale), &mut pixmap.as_mut());
+
+    // tiny-skia works with premultiplied alpha, images do not
+    let data = pixmap.pixels()
+        .iter()
+        .flat_map(|p| {
+            let c = p.demultiply();
+            [c.red(), c.green(), c.blue(), c.alpha()]
+        })
+        .collect();
+
+    RgbaImage::from_raw(size.width(), size.height(), data)
+        .map(DynamicImage::ImageRgba8)
+        .ok_or(SvgError::Size(dpi))
+}
+
+#[derive(Default, TypePath)]
+pub struct SvgLoader;
+
+impl AssetLoader for SvgLoader {
+    type Asset = Image;
+    type Settings = RasterSettings;
+    type Error = SvgError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        settings: &RasterSettings,
+        _load_context: &mut LoadContext<'_>
+    ) -> Result<Image, SvgError>
+    {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes).await?;
+
+        let img = rasterize(&bytes, settings.dpi, system_fonts())?;
+        Ok(Image::from_dynamic(img, true, RenderAssetUsages::default()))
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["svg", "svgz"]
+    }
+}
@@ -3,7 +3,7 @@ use bevy::{
         component::Component,
         event::{EntityEvent, Event},
         observer::On,
-        prelude::{Commands, Entity, Query, RelationshipTarget, Resource, With, Without}
+        prelude::{Commands, Entity, Query, RelationshipTarget, Res, Resource, With, Without}
     },
     prelude::{debug, Result}
 };
@@ -12,7 +12,8 @@ use tracing::instrument;
 
 use crate::{
     edittype::EditType,
-    keys::KeyBinding
+    keys::KeyBinding,
+    log_header::{LogCreated, unix_now}
 };
 
 #[derive(AsRef, Resource)]
@@ -36,6 +37,24 @@ pub struct Edits(Vec<Entity>);
 #[derive(Event)]
 pub struct EditsComplete;
 
+// a saved edit cursor, as child indices from the root down to the cursor
+#[derive(Resource)]
+pub struct LogCursor(pub Vec<usize>);
+
+// a place in the edit log: the group holding the cursor and the edit just
+// before it; an edit done after an undo is a new entity, so gets a new mark
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CursorMark(Entity, Option<Entity>);
+
+impl CursorMark {
+    pub fn new(group: Entity, edits: &Edits, edit_index: &EditIndex) -> Self {
+        CursorMark(
+            group,
+            edit_index.0.checked_sub(1).map(|i| edits.0[i])
+        )
+    }
+}
+
 pub fn handle_do<E>(
     mut edits_query: Query<(Entity, &mut Edits, &mut EditIndex)>,
     edit_type: EditType,
@@ -252,13 +271,22 @@ pub struct RedoAllEvent;
 #[instrument(skip_all)]
 pub fn on_redo_all(
     _evt: On<RedoAllEvent>,
+    cursor: Option<Res<LogCursor>>,
     root_query: Query<(Entity, &Edits), Without<EditOf>>,
     edits_query: Query<(Entity, &Edits, &EditIndex, Option<&EditOf>)>,
     parent_query: Query<(&Edits, Option<&EditOf>), Without<EditIndex>>,
-    commands: Commands
+    group_query: Query<&Edits>,
+    mut commands: Commands
 ) -> Result
 {
-    handle_redo_all(root_query, edits_query, parent_query, commands)
+    if let Some(cursor) = cursor {
+        // a loaded log said where its cursor was; redo only that far
+        commands.remove_resource::<LogCursor>();
+        handle_redo_to(&cursor.0, root_query, edits_query, group_query, commands)
+    }
+    else {
+        handle_redo_all(root_query, edits_query, parent_query, commands)
+    }
 }
 
 #[derive(EntityEvent)]
@@ -423,6 +451,67 @@ pub fn handle_redo_all(
     Ok(())
 }
 
+pub fn handle_redo_to(
+    cursor: &[usize],
+    root_query: Query<(Entity, &Edits), Without<EditOf>>,
+    edits_query: Query<(Entity, &Edits, &EditIndex, Option<&EditOf>)>,
+    group_query: Query<&Edits>,
+    mut commands: Commands
+) -> Result
+{
+    debug!("handle_redo_to");
+
+    let Some((&cursor_idx, path)) = cursor.split_last() else {
+        return Err("log cursor is empty".into());
+    };
+
+    // find the group holding the cursor before changing anything
+    let (root_entity, root_edits) = root_query.single()?;
+
+    let mut groups = vec![ (root_entity, root_edits, 0) ];
+
+    for &idx in path {
+        let (_, edits, _) = groups.last().expect("root is always present");
+
+        let child = *edits.0.get(idx)
+            .ok_or("log cursor is out of range")?;
+
+        groups.last_mut().expect("root is always present").2 = idx;
+        groups.push((child, group_query.get(child)?, 0));
+    }
+
+    let (cursor_entity, cursor_len) = groups.last()
+        .map(|(e, edits, _)| (*e, edits.0.len()))
+        .expect("root is always present");
+
+    if cursor_idx > cursor_len {
+        return Err("log cursor is out of range".into());
+    }
+
+    groups.last_mut().expect("root is always present").2 = cursor_idx;
+
+    // remove the old edit cursor
+    let (edits_entity, ..) = edits_query.single()?;
+
+    commands.get_entity(edits_entity)?
+        .remove::<EditIndex>();
+
+    // redo everything before the path down to the cursor
+    for (_, edits, idx) in &groups {
+        edits.0[..*idx]
+            .iter()
+            .for_each(|&e| commands.trigger(RedoEvent { entity: e }));
+    }
+
+    // set the edit cursor where it was saved
+    commands.get_entity(cursor_entity)?
+        .insert(EditIndex(cursor_idx));
+
+    commands.trigger(EditsComplete);
+
+    Ok(())
+}
+
 #[instrument(skip_all)]
 pub fn on_undo(
      evt: On<UndoEvent>,
@@ -495,11 +584,15 @@ pub fn on_group_redo(
     Ok(())
 }
 
-pub fn init_log(mut commands: Commands)
+pub fn init_log(mut commands: Commands) -> Result
 {
     // create the log root
     commands.spawn((
         Edits::default(),
         EditIndex::default()
     ));
+
+    // a loaded log replaces this with its own creation time
+    commands.insert_resource(LogCreated(unix_now()?));
+    Ok(())
 }
@@ -8,23 +8,26 @@ use bevy::{
 use serde::{
     Deserialize, Deserializer,
     de::{
-        DeserializeSeed, MapAccess, SeqAccess, Visitor,
+        self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor,
         value::MapAccessDeserializer
     }
 };
 use std::{
     fmt,
-    fs::File,
-    io::BufReader
+    fs
 };
 use tracing::instrument;
 
 use crate::{
     LogPath,
+    container,
     edittype::EditType,
-    gamebox::{GameBox, GridDefinition},
+    gamebox::{GameBox, GameBoxInfo},
     grid,
-    log::{EditOf, Edits, EditsComplete},
+    log::{EditOf, Edits, EditsComplete, LogCursor},
+    log_error::{EditPath, LogError},
+    log_header::{check_header, LogCreated, LogHeader},
+    map,
     object::NextObjectId,
     piece::{
         self,
@@ -33,9 +36,11 @@ use crate::{
         delete::DeleteEdit,
         flip::FlipEdit,
         r#move::MoveEdit,
+        property::SetPropertyEdit,
         rotate::RotateEdit,
         splice::SpliceEdit
     },
+    recovery::{RecoveryPath, ReplayRecovery},
     surface
 };
 
@@ -46,6 +51,10 @@ enum Item {
     CreateSurface(surface::create::CreateEdit),
     #[serde(rename = "create_grid")]
     CreateGrid(grid::create::CreateEdit),
+    #[serde(rename = "create_map")]
+    CreateMap(map::create::CreateEdit),
+    #[serde(rename = "create_container")]
+    CreateContainer(container::create::CreateEdit),
 
     Clone(CloneEdit),
     Create(CreateEdit),
@@ -53,6 +62,8 @@ enum Item {
     Flip(FlipEdit),
     Move(MoveEdit),
     Rotate(RotateEdit),
+    #[serde(rename = "set_property")]
+    SetProperty(SetPropertyEdit),
     Splice(SpliceEdit),
     #[serde(untagged)]
     Group
@@ -122,6 +133,12 @@ impl<'de> Visitor<'de> for ItemVisitor<'_, '_, '_> {
                 Item::CreateGrid(ed) => {
                     ec.insert((EditType::CreateGrid, edof, ed));
                 },
+                Item::CreateMap(ed) => {
+                    ec.insert((EditType::CreateMap, edof, ed));
+                },
+                Item::CreateContainer(ed) => {
+                    ec.insert((EditType::CreateContainer, edof, ed));
+                },
                 Item::Clone(ed) => {
                     ec.insert((EditType::Clone, edof, ed));
                 },
@@ -143,6 +160,9 @@ impl<'de> Visitor<'de> for ItemVisitor<'_, '_, '_> {
                 Item::Rotate(ed) => {
                     ec.insert((EditType::Rotate, edof, ed));
                 },
+                Item::SetProperty(ed) => {
+                    ec.insert((EditType::SetProperty, edof, ed));
+                },
                 Item::Splice(ed) => {
                     ec.insert((EditType::Splice, edof, ed));
                 }
@@ -160,30 +180,131 @@ impl<'de> Visitor<'de> for ItemVisitor<'_, '_, '_> {
     }
 }
 
+// the log root is either a bare group or a group with its cursor
+struct LogSeed<'c, 'w, 's> {
+    entity: Entity,
+    commands: &'c mut Commands<'w, 's>
+}
+
+impl<'de> DeserializeSeed<'de> for LogSeed<'_, '_, '_> {
+    type Value = Option<Vec<usize>>;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>
+    {
+        deserializer.deserialize_any(LogVisitor {
+            entity: self.entity,
+            commands: self.commands
+        })
+    }
+}
+
+struct LogVisitor<'c, 'w, 's> {
+    entity: Entity,
+    commands: &'c mut Commands<'w, 's>
+}
+
+impl<'de> Visitor<'de> for LogVisitor<'_, '_, '_> {
+    type Value = Option<Vec<usize>>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a sequence of edits or a log with a cursor")
+    }
+
+    fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>
+    {
+        ItemVisitor {
+            entity: self.entity,
+            commands: self.commands
+        }.visit_seq(seq)?;
+
+        Ok(None)
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>
+    {
+        let mut cursor = None;
+        let mut has_edits = false;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "cursor" => {
+                    cursor = Some(map.next_value::<Vec<usize>>()?);
+                },
+                "edits" => {
+                    let seed = ItemSeed {
+                        entity: self.entity,
+                        commands: &mut *self.commands
+                    };
+
+                    if !matches!(map.next_value_seed(seed)?, Item::Group) {
+                        return Err(de::Error::custom(LogError::MalformedGroup {
+                            index: 0,
+                            path: EditPath::default()
+                        }));
+                    }
+
+                    has_edits = true;
+                },
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        if !has_edits {
+            return Err(de::Error::missing_field("edits"));
+        }
+
+        Ok(cursor)
+    }
+}
+
 #[instrument(skip_all)]
 pub fn deserialize_edits(
     log_path: Res<LogPath>,
+    recovery_path: Res<RecoveryPath>,
+    replay_recovery: Res<ReplayRecovery>,
+    gamebox_info: Res<GameBoxInfo>,
     root_query: Query<Entity, (With<Edits>, Without<EditOf>)>,
     mut commands: Commands
 ) -> Result {
     debug!("");
 
-    let Some(path) = log_path.0.as_ref() else { return Ok(()); };
+    let path = if replay_recovery.0 {
+        Some(&recovery_path.0)
+    }
+    else {
+        log_path.0.as_ref()
+    };
+
+    let Some(path) = path else { return Ok(()); };
 
-    let file = File::open(path)?;
-    let reader = BufReader::new(file);
+    let log = fs::read_to_string(path)?;
+
+    // a bare array is a log from before logs had headers
+    if !log.trim_start().starts_with('[') {
+        let header: LogHeader = serde_json::from_str(&log)?;
+        check_header(&header, &gamebox_info)?;
+        commands.insert_resource(LogCreated(header.created));
+    }
 
     let root_entity = root_query.single()?;
 
-    let r = ItemSeed {
+    let r = LogSeed {
         entity: root_entity,
         commands: &mut commands
     };
 
-    let mut d = serde_json::Deserializer::from_reader(reader);
-    if !matches!(r.deserialize(&mut d)?, Item::Group) {
-        // TODO: define a real error type
-        return Err("log root must be a group".into());
+    let mut d = serde_json::Deserializer::from_str(&log);
+    if let Some(cursor) = r.deserialize(&mut d)? {
+        // restore the saved cursor instead of redoing to the end
+        commands.insert_resource(LogCursor(cursor));
     }
 
     commands.trigger(EditsComplete);
@@ -194,6 +315,8 @@ pub fn deserialize_edits(
 pub fn update_next_object_id(
     surface_create_q: Query<&surface::create::CreateEdit>,
     grid_create_q: Query<&grid::create::CreateEdit>,
+    map_create_q: Query<&map::create::CreateEdit>,
+    container_create_q: Query<&container::create::CreateEdit>,
     gamebox: Res<GameBox>,
     piece_clone_q: Query<&piece::create::CreateEdit>,
     piece_create_q: Query<&piece::clone::CloneEdit>,
@@ -204,12 +327,11 @@ pub fn update_next_object_id(
     if let Some(max_object_id) = surface_create_q.iter().map(|ed| ed.object_id)
         .chain(
             grid_create_q.iter().map(|ed|
-                ed.object_id + match &gamebox.grid[&ed.type_id] {
-                    GridDefinition::Hex(h) => h.cols * h.rows,
-                    GridDefinition::Rect(r) => r.cols * r.rows
-                }
+                ed.object_id + gamebox.grid[&ed.type_id].cell_count()
             )
         )
+        .chain(map_create_q.iter().map(|ed| ed.object_id))
+        .chain(container_create_q.iter().map(|ed| ed.object_id))
         .chain(piece_clone_q.iter().map(|ed| ed.object_id))
         .chain(piece_create_q.iter().map(|ed| ed.object_id))
         .max()
@@ -0,0 +1,86 @@
+use std::fmt;
+
+// child indices from the log root down to an edit
+#[derive(Clone, Debug, Default)]
+pub struct EditPath(pub Vec<usize>);
+
+impl fmt::Display for EditPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "root");
+        }
+
+        let mut i = self.0.iter();
+        if let Some(first) = i.next() {
+            write!(f, "{first}")?;
+        }
+
+        for idx in i {
+            write!(f, "/{idx}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum LogError {
+    #[error("log format {found} is newer than supported format {supported}")]
+    Version {
+        found: u32,
+        supported: u32
+    },
+    #[error("log was made with gamebox {log:?}, not {loaded:?}")]
+    GameBox {
+        log: String,
+        loaded: String
+    },
+    #[error("edit {index} at {path}: malformed group")]
+    MalformedGroup {
+        index: usize,
+        path: EditPath
+    },
+    #[error("edit {index} at {path}: unknown object id {object_id}")]
+    UnknownObjectId {
+        index: usize,
+        path: EditPath,
+        object_id: u32
+    },
+    #[error("edit {index} at {path}: object id {object_id} is already in use")]
+    DuplicateObjectId {
+        index: usize,
+        path: EditPath,
+        object_id: u32
+    },
+    #[error("edit {index} at {path}: unknown piece type id {type_id}")]
+    UnknownPieceType {
+        index: usize,
+        path: EditPath,
+        type_id: u32
+    },
+    #[error("edit {index} at {path}: unknown grid type id {type_id}")]
+    UnknownGridType {
+        index: usize,
+        path: EditPath,
+        type_id: u32
+    },
+    #[error("edit {index} at {path}: piece type {type_id} has no face {face}")]
+    UnknownFace {
+        index: usize,
+        path: EditPath,
+        type_id: u32,
+        face: usize
+    },
+    #[error("edit {index} at {path}: unknown map type id {type_id}")]
+    UnknownMapType {
+        index: usize,
+        path: EditPath,
+        type_id: u32
+    },
+    #[error("edit {index} at {path}: unknown container type id {type_id}")]
+    UnknownContainerType {
+        index: usize,
+        path: EditPath,
+        type_id: u32
+    }
+}
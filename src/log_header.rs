@@ -0,0 +1,56 @@
+use bevy::prelude::{Resource, warn};
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, SystemTimeError, UNIX_EPOCH};
+
+use crate::{
+    gamebox::GameBoxInfo,
+    log_error::LogError
+};
+
+// version 0 is a bare array of edits with no header
+pub const LOG_FORMAT_VERSION: u32 = 1;
+
+#[derive(Clone, Copy, Debug, Resource)]
+pub struct LogCreated(pub u64);
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LogHeader {
+    pub format: u32,
+    pub gamebox: GameBoxInfo,
+    pub created: u64
+}
+
+pub fn unix_now() -> Result<u64, SystemTimeError> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+pub fn check_header(
+    header: &LogHeader,
+    gamebox: &GameBoxInfo
+) -> Result<(), LogError>
+{
+    if header.format > LOG_FORMAT_VERSION {
+        return Err(LogError::Version {
+            found: header.format,
+            supported: LOG_FORMAT_VERSION
+        });
+    }
+
+    if header.gamebox.hash != gamebox.hash {
+        // a different name and different contents is a different gamebox
+        if header.gamebox.name != gamebox.name {
+            return Err(LogError::GameBox {
+                log: header.gamebox.name.clone(),
+                loaded: gamebox.name.clone()
+            });
+        }
+
+        // the same name with different contents is probably an edited one
+        warn!(
+            "log was made with a different version of gamebox {:?}",
+            gamebox.name
+        );
+    }
+
+    Ok(())
+}
@@ -1,33 +1,54 @@
 use bevy::{
     ecs::{
         component::Component,
-        prelude::{RelationshipTarget, Without},
-        world::DeferredWorld
+        prelude::{RelationshipTarget, Resource, Without},
+        world::{DeferredWorld, World}
     },
-    prelude::{Entity, EntityRef, Result}
+    prelude::{Entity, EntityRef, info, Result}
 };
+use derive_more::AsRef;
 use serde::{
     Serialize, Serializer,
     ser::SerializeSeq
 };
-use std::io::Write;
+use std::{
+    ffi::OsStr,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf}
+};
 
 use crate::{
+    GameBoxPath, LogPath,
+    config::Config,
+    container,
     edittype::EditType,
+    gamebox::GameBoxInfo,
     grid,
-    log::{EditIndex, EditOf, Edits},
+    keys::KeyBinding,
+    log::{CursorMark, EditIndex, EditOf, Edits},
+    log_header::{LOG_FORMAT_VERSION, LogCreated, LogHeader, unix_now},
+    map,
     piece::{
         clone::CloneEdit,
         create::CreateEdit,
         delete::DeleteEdit,
         flip::FlipEdit,
         r#move::MoveEdit,
+        property::SetPropertyEdit,
         rotate::RotateEdit,
         splice::SpliceEdit
     },
+    recovery::{RecoveryPath, SavedCursor, remove_recovery_file},
     surface
 };
 
+#[derive(AsRef, Resource)]
+pub struct SaveKey(pub KeyBinding);
+
+#[derive(AsRef, Resource)]
+pub struct SaveAsKey(pub KeyBinding);
+
 trait SerializeEdit {
     type Error: serde::ser::Error;
 
@@ -62,7 +83,7 @@ struct GroupProxy<'e, 's, 'w>(
     Entity,
     &'e Edits,
     &'s [(Entity, usize)],
-    &'w DeferredWorld<'w>
+    &'w World
 );
 
 impl Serialize for GroupProxy<'_, '_, '_> {
@@ -88,6 +109,8 @@ impl Serialize for GroupProxy<'_, '_, '_> {
             match etype {
                 EditType::CreateSurface => seq.serialize_edit::<surface::create::CreateEdit>(eref)?,
                 EditType::CreateGrid => seq.serialize_edit::<grid::create::CreateEdit>(eref)?,
+                EditType::CreateMap => seq.serialize_edit::<map::create::CreateEdit>(eref)?,
+                EditType::CreateContainer => seq.serialize_edit::<container::create::CreateEdit>(eref)?,
                 EditType::Clone => seq.serialize_edit::<CloneEdit>(eref)?,
                 EditType::Create => seq.serialize_edit::<CreateEdit>(eref)?,
                 EditType::Delete => seq.serialize_edit::<DeleteEdit>(eref)?,
@@ -103,6 +126,7 @@ impl Serialize for GroupProxy<'_, '_, '_> {
                 )?,
                 EditType::Move => seq.serialize_edit::<MoveEdit>(eref)?,
                 EditType::Rotate => seq.serialize_edit::<RotateEdit>(eref)?,
+                EditType::SetProperty => seq.serialize_edit::<SetPropertyEdit>(eref)?,
                 EditType::Splice => seq.serialize_edit::<SpliceEdit>(eref)?
             }
         };
@@ -111,21 +135,29 @@ impl Serialize for GroupProxy<'_, '_, '_> {
     }
 }
 
-pub fn serialize_edits(world: DeferredWorld) -> Result
-{
-    let mut writer = std::io::stdout();
-    writeln!(&mut writer)?;
+#[derive(Serialize)]
+struct LogFile<'h, 'e, 's, 'w> {
+    #[serde(flatten)]
+    header: &'h LogHeader,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cursor: Option<Vec<usize>>,
+    edits: GroupProxy<'e, 's, 'w>
+}
 
+pub fn write_edits<W>(world: &World, mut writer: W) -> Result
+where
+    W: Write
+{
     // find the root
     let mut root_query = world.try_query_filtered::<(Entity, &Edits), Without<EditOf>>().expect("no query");
 
-    let (root_entity, root_edits) = root_query.single(&world)?;
+    let (root_entity, root_edits) = root_query.single(world)?;
 
     // find the edit cursor
     let mut edit_index_query = world.try_query::<(Entity, &EditIndex)>()
         .expect("no query");
 
-    let (cur_entity, cur_idx) = edit_index_query.single(&world)?;
+    let (cur_entity, cur_idx) = edit_index_query.single(world)?;
 
     // find the redo boundary for the edit cursor
     let mut parent_query = world.try_query::<&EditOf>()
@@ -134,21 +166,113 @@ pub fn serialize_edits(world: DeferredWorld) -> Result
         .expect("no query");
 
     let mut stops = vec![ (cur_entity, cur_idx.0) ];
+    let mut cursor = vec![ cur_idx.0 ];
     let mut e = cur_entity;
     while e != root_entity {
-        e = parent_query.get(&world, e)?.0;
-        let edits = parent_edits_query.get(&world, e)?;
+        let child = e;
+        e = parent_query.get(world, e)?.0;
+        let edits = parent_edits_query.get(world, e)?;
 
         let idx = edits.iter()
-            .position(|ed| e == ed)
-            .expect("child must exist in parent") + 1;
+            .position(|ed| child == ed)
+            .expect("child must exist in parent");
+
+        stops.push((e, idx + 1));
+        cursor.push(idx);
+    }
+
+    let keep_redo = world.get_resource::<Config>()
+        .is_some_and(|c| c.log.keep_redo);
 
-        stops.push((e, idx));
+    // keeping the redos means writing the whole tree, and the cursor in it
+    let (cursor, stops): (_, &[(Entity, usize)]) = if keep_redo {
+        cursor.reverse();
+        (Some(cursor), &[])
     }
+    else {
+        (None, &stops[..])
+    };
+
+    let gamebox = world.get_resource::<GameBoxInfo>()
+        .ok_or("gamebox is not loaded")?;
 
-    let g = GroupProxy(root_entity, root_edits, &stops, &world);
+    let created = match world.get_resource::<LogCreated>() {
+        Some(created) => created.0,
+        None => unix_now()?
+    };
 
-    serde_json::to_writer(&mut writer, &g)?;
+    let log = LogFile {
+        header: &LogHeader {
+            format: LOG_FORMAT_VERSION,
+            gamebox: gamebox.clone(),
+            created
+        },
+        cursor,
+        edits: GroupProxy(root_entity, root_edits, stops, world)
+    };
+
+    serde_json::to_writer(&mut writer, &log)?;
     writeln!(&mut writer)?;
     Ok(())
 }
+
+pub fn write_edits_atomic(world: &World, path: &Path) -> Result {
+    // write to a temporary file beside the log, then swap it into place
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
+
+    let file = File::create(&tmp_path)?;
+    let mut writer = BufWriter::new(&file);
+    write_edits(world, &mut writer)?;
+    writer.flush()?;
+    drop(writer);
+    file.sync_all()?;
+
+    fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+fn new_log_path(gamebox_path: &Path) -> Result<PathBuf> {
+    let stem = gamebox_path.file_stem()
+        .and_then(OsStr::to_str)
+        .unwrap_or("log");
+
+    let secs = unix_now()?;
+
+    Ok(gamebox_path.with_file_name(format!("{stem}-{secs}.json")))
+}
+
+fn save_to(mut world: DeferredWorld, path: PathBuf) -> Result {
+    write_edits_atomic(&world, &path)?;
+    info!("saved log to {}", path.display());
+
+    // the saved log supersedes any autosaved edits
+    remove_recovery_file(&world.resource::<RecoveryPath>().0)?;
+
+    // later saves go to the same place
+    world.resource_mut::<LogPath>().0 = Some(path);
+
+    // the edits as they are now need no recovering
+    let mut cursor_query = world.try_query::<(Entity, &Edits, &EditIndex)>()
+        .expect("no query");
+    let mark = cursor_query.single(&world)
+        .map(|(e, edits, idx)| CursorMark::new(e, edits, idx))?;
+    world.resource_mut::<SavedCursor>().0 = Some(mark);
+
+    Ok(())
+}
+
+pub fn save_edits(world: DeferredWorld) -> Result {
+    let path = match &world.resource::<LogPath>().0 {
+        Some(path) => path.clone(),
+        None => new_log_path(&world.resource::<GameBoxPath>().0)?
+    };
+
+    save_to(world, path)
+}
+
+pub fn save_edits_as(world: DeferredWorld) -> Result {
+    let path = new_log_path(&world.resource::<GameBoxPath>().0)?;
+    save_to(world, path)
+}
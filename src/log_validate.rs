@@ -0,0 +1,280 @@
+use bevy::{
+    ecs::{
+        error::Result,
+        prelude::{Entity, RelationshipTarget, Without},
+        world::World
+    },
+    prelude::debug
+};
+use std::collections::HashSet;
+use tracing::instrument;
+
+use crate::{
+    container,
+    edittype::EditType,
+    gamebox::{GameBox, GridDefinition},
+    grid,
+    log::{EditOf, Edits},
+    log_error::{EditPath, LogError},
+    map,
+    object::ObjectIdMap,
+    piece::{
+        clone::CloneEdit,
+        create::CreateEdit,
+        delete::DeleteEdit,
+        flip::FlipEdit,
+        r#move::MoveEdit,
+        property::SetPropertyEdit,
+        rotate::RotateEdit,
+        splice::SpliceEdit
+    },
+    surface
+};
+
+// replays the object ids a log creates and removes without touching the world
+struct Validator<'w> {
+    world: &'w World,
+    gamebox: &'w GameBox,
+    ids: HashSet<u32>,
+    index: usize,
+    path: EditPath
+}
+
+impl Validator<'_> {
+    fn require(&self, object_id: u32) -> Result<(), LogError> {
+        if self.ids.contains(&object_id) {
+            Ok(())
+        }
+        else {
+            Err(LogError::UnknownObjectId {
+                index: self.index,
+                path: self.path.clone(),
+                object_id
+            })
+        }
+    }
+
+    fn add(&mut self, object_id: u32) -> Result<(), LogError> {
+        if self.ids.insert(object_id) {
+            Ok(())
+        }
+        else {
+            Err(LogError::DuplicateObjectId {
+                index: self.index,
+                path: self.path.clone(),
+                object_id
+            })
+        }
+    }
+
+    fn remove(&mut self, object_id: u32) -> Result<(), LogError> {
+        self.require(object_id)?;
+        self.ids.remove(&object_id);
+        Ok(())
+    }
+
+    fn require_piece_type(&self, type_id: u32) -> Result<(), LogError> {
+        if self.gamebox.piece.contains_key(&type_id) {
+            Ok(())
+        }
+        else {
+            Err(LogError::UnknownPieceType {
+                index: self.index,
+                path: self.path.clone(),
+                type_id
+            })
+        }
+    }
+
+    fn require_face(&self, type_id: u32, face: usize) -> Result<(), LogError> {
+        self.require_piece_type(type_id)?;
+
+        if face < self.gamebox.piece[&type_id].faces.len().max(1) {
+            Ok(())
+        }
+        else {
+            Err(LogError::UnknownFace {
+                index: self.index,
+                path: self.path.clone(),
+                type_id,
+                face
+            })
+        }
+    }
+
+    fn grid_type(&self, type_id: u32) -> Result<&GridDefinition, LogError> {
+        self.gamebox.grid.get(&type_id)
+            .ok_or_else(|| LogError::UnknownGridType {
+                index: self.index,
+                path: self.path.clone(),
+                type_id
+            })
+    }
+
+    fn require_map_type(&self, type_id: u32) -> Result<(), LogError> {
+        if self.gamebox.map.contains_key(&type_id) {
+            Ok(())
+        }
+        else {
+            Err(LogError::UnknownMapType {
+                index: self.index,
+                path: self.path.clone(),
+                type_id
+            })
+        }
+    }
+
+    fn require_container_type(&self, type_id: u32) -> Result<(), LogError> {
+        if self.gamebox.container.contains_key(&type_id) {
+            Ok(())
+        }
+        else {
+            Err(LogError::UnknownContainerType {
+                index: self.index,
+                path: self.path.clone(),
+                type_id
+            })
+        }
+    }
+
+    fn validate_group(&mut self, edits: &Edits) -> Result<(), LogError> {
+        for (i, entity) in edits.iter().enumerate() {
+            self.path.0.push(i);
+            self.validate_edit(entity)?;
+            self.path.0.pop();
+        }
+        Ok(())
+    }
+
+    fn validate_edit(&mut self, entity: Entity) -> Result<(), LogError> {
+        let e = self.world.entity(entity);
+
+        let Some(edit_type) = e.get::<EditType>() else {
+            return Err(LogError::MalformedGroup {
+                index: self.index,
+                path: self.path.clone()
+            });
+        };
+
+        match edit_type {
+            EditType::CreateSurface => {
+                if let Some(cr) = e.get::<surface::create::CreateEdit>() {
+                    self.add(cr.object_id)?;
+                }
+            },
+            EditType::CreateGrid => {
+                if let Some(cr) = e.get::<grid::create::CreateEdit>() {
+                    self.require(cr.parent_id)?;
+                    self.add(cr.object_id)?;
+
+                    // every grid reserves object ids for its cells, as
+                    // creating it and update_next_object_id do
+                    for oid in 1..=self.grid_type(cr.type_id)?.cell_count() {
+                        self.add(cr.object_id + oid)?;
+                    }
+                }
+            },
+            EditType::CreateMap => {
+                if let Some(cr) = e.get::<map::create::CreateEdit>() {
+                    self.require_map_type(cr.type_id)?;
+                    self.require(cr.parent_id)?;
+                    self.add(cr.object_id)?;
+                }
+            },
+            EditType::CreateContainer => {
+                if let Some(cr) = e.get::<container::create::CreateEdit>() {
+                    self.require_container_type(cr.type_id)?;
+                    self.require(cr.parent_id)?;
+                    self.add(cr.object_id)?;
+                }
+            },
+            EditType::Clone => {
+                if let Some(cl) = e.get::<CloneEdit>() {
+                    self.require(cl.source_id)?;
+                    self.add(cl.object_id)?;
+                }
+            },
+            EditType::Create => {
+                if let Some(cr) = e.get::<CreateEdit>() {
+                    self.require_face(cr.type_id, cr.face)?;
+                    self.require(cr.parent_id)?;
+                    self.add(cr.object_id)?;
+                }
+            },
+            EditType::Delete => {
+                if let Some(del) = e.get::<DeleteEdit>() {
+                    self.require_piece_type(del.type_id)?;
+                    self.require(del.parent_id)?;
+                    self.remove(del.object_id)?;
+                }
+            },
+            EditType::Flip => {
+                if let Some(flip) = e.get::<FlipEdit>() {
+                    self.require(flip.object_id)?;
+                }
+            },
+            EditType::Group => {
+                let Some(edits) = e.get::<Edits>() else {
+                    return Err(LogError::MalformedGroup {
+                        index: self.index,
+                        path: self.path.clone()
+                    });
+                };
+
+                self.index += 1;
+                return self.validate_group(edits);
+            },
+            EditType::Move => {
+                if let Some(mov) = e.get::<MoveEdit>() {
+                    self.require(mov.object_id)?;
+                    self.require(mov.src_parent_id)?;
+                    self.require(mov.dst_parent_id)?;
+                }
+            },
+            EditType::Rotate => {
+                if let Some(rot) = e.get::<RotateEdit>() {
+                    self.require(rot.object_id)?;
+                }
+            },
+            EditType::SetProperty => {
+                if let Some(set) = e.get::<SetPropertyEdit>() {
+                    self.require(set.object_id)?;
+                }
+            },
+            EditType::Splice => {
+                if let Some(spl) = e.get::<SpliceEdit>() {
+                    self.require(spl.object_id)?;
+                    self.require(spl.src_parent_id)?;
+                    self.require(spl.dst_parent_id)?;
+
+                    for oid in [spl.src_child_id, spl.dst_child_id].into_iter().flatten() {
+                        self.require(oid)?;
+                    }
+                }
+            }
+        }
+
+        self.index += 1;
+        Ok(())
+    }
+}
+
+#[instrument(skip_all)]
+pub fn validate_edits(world: &mut World) -> Result {
+    debug!("");
+
+    let mut roots = world.query_filtered::<&Edits, Without<EditOf>>();
+    let world = &*world;
+    let root = roots.single(world)?;
+
+    let mut v = Validator {
+        world,
+        gamebox: world.resource::<GameBox>(),
+        ids: world.resource::<ObjectIdMap>().0.keys().copied().collect(),
+        index: 0,
+        path: EditPath::default()
+    };
+
+    v.validate_group(root)?;
+    Ok(())
+}
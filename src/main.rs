@@ -1,10 +1,7 @@
 use bevy::{
     DefaultPlugins,
-    app::{App, PluginGroup, Update},
-    asset::{
-        AssetApp,
-        io::AssetSourceBuilder
-    },
+    app::{App, AppExit, Last, PluginGroup, Update},
+    asset::AssetApp,
     ecs::{
         change_detection::{Res, ResMut},
         entity::Entity,
@@ -19,17 +16,23 @@ use bevy::{
         mouse::AccumulatedMouseScroll
     },
     picking::mesh_picking::MeshPickingPlugin,
-    prelude::{AppExtStates, IntoScheduleConfigs, in_state, NextState, OnEnter, Resource, Time, trace, Window, WindowPlugin}
+    prelude::{AppExtStates, IntoScheduleConfigs, in_state, NextState, OnEnter, OnExit, Resource, Time, Timer, TimerMode, trace, Window, WindowPlugin}
 };
+use clap::Parser;
 use std::{
-    path::PathBuf,
+    path::{Path, PathBuf},
+    process::ExitCode,
     time::Duration
 };
 
 mod actionfunc;
 mod angle;
+mod archive;
 mod assets;
+mod check;
+mod cli;
 mod config;
+mod container;
 mod context_menu;
 mod debug;
 mod double_click;
@@ -38,13 +41,23 @@ mod edittype;
 mod gamebox;
 mod grid;
 mod keys;
+mod lint;
+mod loader;
 mod log;
 mod log_deserialize;
+mod log_error;
+mod log_header;
 mod log_serialize;
+mod log_validate;
+mod map;
 mod maxz;
 mod object;
 mod piece;
+mod raster;
+mod recovery;
+mod scenario;
 mod select;
+mod setup;
 mod stack;
 mod state;
 mod surface;
@@ -52,20 +65,28 @@ mod title;
 mod util;
 mod view;
 mod view_adjust;
+mod watch;
 
 use crate::{
-    assets::{LoadingHandles, load_assets, mark_images_loaded},
-    config::{Config, load_config},
+    archive::{GameBoxFiles, read_gamebox},
+    assets::{GameBoxWatch, LoadingHandles, load_assets, mark_images_loaded, reload_gamebox},
+    cli::{Cli, Command},
+    config::{Config, ConfigSources, load_config, reload_config},
     context_menu::{ContextMenuState, open_context_menu, close_context_menus, trigger_close_context_menus_key, trigger_close_context_menus_press, trigger_close_context_menus_wheel},
     debug::DebugState,
     double_click::{DoubleClickThreshold, DoubleClickTimer, tick_double_click_timer},
     drag::DragOrigin,
-    grid::{show_grid_bounding_boxes, hide_grid_bounding_boxes},
+    gamebox::{GameBox, template},
+    grid::{show_grid_bounding_boxes, hide_grid_bounding_boxes, reattach_pieces, respawn_grids},
     keys::{cfg_input_pressed, cfg_input_just_pressed},
+    loader::loader_plugin,
     log::{handle_redo_over, handle_undo, init_log, on_group_close, on_group_open, on_group_redo, on_group_undo, on_redo, on_redo_all, on_undo, RedoAllEvent, RedoKey, UndoKey},
     log_deserialize::{deserialize_edits, update_next_object_id},
-    log_serialize::serialize_edits,
+    log_serialize::{save_edits, save_edits_as, SaveAsKey, SaveKey},
+    log_validate::validate_edits,
     object::{NextObjectId, ObjectIdMap},
+    raster::{RasterScales, RasterTasks, finish_rasters, fit_vector_sprites, request_rasters, rescale_reloaded_layouts},
+    recovery::{AutosaveDirty, AutosaveTimer, autosave_due, autosave_edits, clear_recovery_on_exit, mark_autosave_dirty, mark_loaded_log_saved, offer_recovery, RecoveryPath, recovery_is_newer, recovery_path, ReplayRecovery, SavedCursor, tick_autosave_timer},
     view_adjust::{
         handle_pan_left, handle_pan_right, handle_pan_up, handle_pan_down, handle_pan_drag,
         handle_rotate_ccw, handle_rotate_cw,
@@ -76,9 +97,12 @@ use crate::{
         ZoomInKey, ZoomOutKey, ZoomResetKey,
         WheelScaleStep
     },
+    scenario::display_scenarios,
     select::{clear_selection, draw_selection_rect, selection_rect_drag_start, selection_rect_drag, selection_rect_drag_end, Selected, SelectionRect, setup_selection_box, handle_key_selection},
+    setup::{ChooseScenario, ScenarioName, find_scenario, seed_setup},
     state::GameState,
-    title::{SplashScreenTimer, display_title}
+    title::{SplashScreenTimer, display_title},
+    watch::FileWatch
 };
 
 #[derive(Resource)]
@@ -87,35 +111,135 @@ pub struct GameBoxPath(pub PathBuf);
 #[derive(Resource)]
 pub struct LogPath(pub Option<PathBuf>);
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let mut args = std::env::args().skip(1);
+#[derive(Debug, thiserror::Error)]
+#[error("gamebox path {0:?} has no parent directory")]
+pub struct GameBoxPathError(PathBuf);
+
+impl GameBoxPath {
+    // the directory holding the gamebox is the root of its asset source
+    pub fn base(&self) -> Result<String, GameBoxPathError> {
+        self.0.parent()
+            .and_then(|p| p.to_str())
+            .map(str::to_owned)
+            .ok_or_else(|| GameBoxPathError(self.0.clone()))
+    }
+}
+
+fn exit_code(exit: AppExit) -> ExitCode {
+    match exit {
+        AppExit::Success => ExitCode::SUCCESS,
+        AppExit::Error(code) => ExitCode::from(code.get())
+    }
+}
+
+fn require_file(what: &str, path: &Path) -> Result<(), String> {
+    if path.is_file() {
+        Ok(())
+    }
+    else {
+        Err(format!("{what} {} does not exist", path.display()))
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run(cli) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: Cli) -> Result<ExitCode, Box<dyn std::error::Error>> {
+    // replay a log without a window and report the result
+    if let Some(Command::Check { gamebox, log }) = cli.command {
+        require_file("gamebox", &gamebox)?;
+        require_file("log", &log)?;
+
+        let exit = check::run_check(GameBoxPath(gamebox), LogPath(Some(log)))?;
+        return Ok(exit_code(exit));
+    }
+
+    // report problems with a gamebox without running it
+    if let Some(Command::Lint { gamebox }) = cli.command {
+        require_file("gamebox", &gamebox)?;
+        return Ok(lint::run_lint(GameBoxPath(gamebox))?);
+    }
+
+    let gamebox_path = GameBoxPath(cli.gamebox.ok_or("no gamebox given")?);
+    require_file("gamebox", &gamebox_path.0)?;
+
+    if let Some(log) = &cli.log {
+        require_file("log", log)?;
+    }
+
+    // a mistyped scenario is reported before any window opens
+    if let Some(name) = &cli.scenario {
+        let gamebox = template::from_str::<GameBox>(&read_gamebox(&gamebox_path.0)?)?;
+        find_scenario(&gamebox, name)?;
+    }
+
+    let log_path = LogPath(cli.log);
+
+    // offer to replay autosaved edits left behind by a crash
+    let recovery_path = RecoveryPath(recovery_path(&gamebox_path.0));
+    let replay_recovery = ReplayRecovery(
+        recovery_is_newer(&recovery_path.0, log_path.0.as_deref()) &&
+        offer_recovery(&recovery_path.0)?
+    );
+
+    // a log or a recovery file has its own setup
+    if cli.scenario.is_some() && (log_path.0.is_some() || replay_recovery.0) {
+        eprintln!("warning: --scenario is ignored when replaying a log or recovery file");
+    }
+
+    let base_path = gamebox_path.base()?;
+    let gamebox_files = GameBoxFiles::new(&gamebox_path)?;
+
+    let config_sources = ConfigSources::new(&gamebox_path.0, cli.config);
 
-// FIXME: unwrap
-    let gamebox_path = GameBoxPath(PathBuf::from(args.next().unwrap()));
+    let gamebox_watch = GameBoxWatch(
+        FileWatch::new(vec![gamebox_path.0.clone()], Duration::from_secs(1))
+    );
 
-    let log_path = LogPath(args.next().map(PathBuf::from));
+    let splash_secs = if cli.no_splash { 0.0 } else { 2.0 };
 
-// FIXME: unwrap
-    let base_path = gamebox_path.0
-        .parent()
-        .unwrap()
-        .to_str()
-        .unwrap()
-        .to_owned();
+    let mut window = Window {
+        title: "V4 Bevy Demo".into(),
+        ..Default::default()
+    };
+
+    if let Some(size) = cli.window_size {
+        window.resolution = size.into();
+    }
 
-    App::new()
+    let exit = App::new()
         .insert_resource(gamebox_path)
         .insert_resource(log_path)
+        .insert_resource(ScenarioName(cli.scenario))
+        .insert_resource(config_sources.watch())
+        .insert_resource(config_sources)
+        .insert_resource(gamebox_watch)
+        .insert_resource(recovery_path)
+        .insert_resource(replay_recovery)
+        .insert_resource(SplashScreenTimer(Timer::from_seconds(splash_secs, TimerMode::Once)))
+        // edits are written as they complete, but at most once a second
+        .insert_resource(AutosaveTimer(Timer::from_seconds(1.0, TimerMode::Once)))
+        .init_resource::<AutosaveDirty>()
+        .init_resource::<SavedCursor>()
+        .init_resource::<RasterScales>()
+        .init_resource::<RasterTasks>()
+        .insert_resource(gamebox_files.clone())
         .register_asset_source(
             base_path.clone(),
-            AssetSourceBuilder::platform_default(&base_path, None)
+            gamebox_files.asset_source()
         )
         .add_plugins((DefaultPlugins
             .set(WindowPlugin {
-                primary_window: Some(Window {
-                    title: "V4 Bevy Demo".into(),
-                    ..Default::default()
-                }),
+                primary_window: Some(window),
                 ..Default::default()
             })
             .set(ImagePlugin {
@@ -129,12 +253,14 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         ))
         .init_state::<GameState>()
         .add_plugins((
+            loader_plugin,
             splash_plugin,
-            game_plugin
+            game_plugin,
+            edit_plugin
         ))
         .run();
 
-    Ok(())
+    Ok(exit_code(exit))
 }
 
 fn splash_plugin(app: &mut App) {
@@ -154,28 +280,48 @@ fn splash_plugin(app: &mut App) {
                     .after(display_title)
                     .after(load_assets)
                     .after(init_log),
+                seed_setup
+                    .after(deserialize_edits),
+                validate_edits
+                    .after(seed_setup),
                 update_next_object_id
-                    .after(deserialize_edits)
+                    .after(validate_edits)
             )
         )
         .add_systems(
             Update,
             (
                 mark_images_loaded,
+                finish_rasters,
                 switch_to_game
             ).run_if(in_state(GameState::Splash))
+        )
+        .add_systems(OnEnter(GameState::Scenario), display_scenarios)
+        .add_systems(
+            OnExit(GameState::Scenario),
+            (
+                validate_edits,
+                update_next_object_id
+                    .after(validate_edits)
+            )
         );
 }
 
 fn switch_to_game(
     mut next: ResMut<NextState<GameState>>,
     loading_handles: Res<LoadingHandles>,
+    choose_scenario: Option<Res<ChooseScenario>>,
     mut timer: ResMut<SplashScreenTimer>,
     time: Res<Time>
 ) {
     if timer.0.tick(time.delta()).is_finished() && loading_handles.0.is_empty()
     {
-        next.set(GameState::Game);
+        next.set(if choose_scenario.is_some() {
+            GameState::Scenario
+        }
+        else {
+            GameState::Game
+        });
     }
 }
 
@@ -211,6 +357,9 @@ fn load_input_settings(
 
     commands.insert_resource(UndoKey(keys.undo));
     commands.insert_resource(RedoKey(keys.redo));
+
+    commands.insert_resource(SaveKey(keys.save));
+    commands.insert_resource(SaveAsKey(keys.save_as));
 }
 
 fn setup_game_resources(mut commands: Commands) {
@@ -225,7 +374,11 @@ fn game_plugin(app: &mut App) {
     app
         .add_systems(
             OnEnter(GameState::Game),
-            display_game
+            (
+                display_game,
+                mark_loaded_log_saved
+            )
+            .chain()
         )
         .init_state::<ContextMenuState>()
         .add_systems(
@@ -272,7 +425,8 @@ fn game_plugin(app: &mut App) {
                 handle_undo.run_if(cfg_input_just_pressed::<UndoKey>),
                 handle_redo_over.run_if(cfg_input_just_pressed::<RedoKey>),
 
-                serialize_edits.run_if(input_just_pressed(KeyCode::KeyL)),
+                save_edits.run_if(cfg_input_just_pressed::<SaveKey>),
+                save_edits_as.run_if(cfg_input_just_pressed::<SaveAsKey>),
                 debug::toggle_debug_state.run_if(input_just_pressed(KeyCode::Escape)),
                 tick_double_click_timer
             )
@@ -282,12 +436,50 @@ fn game_plugin(app: &mut App) {
             Update,
             (
                 piece::flip::on_face_change,
+                piece::label::update_labels,
                 piece::r#move::on_location_change,
+                container::arrange_containers
+                    .after(piece::r#move::on_location_change),
                 piece::r#move::on_stack_change,
                 piece::rotate::on_rotate_change,
             )
             .run_if(in_state(GameState::Game))
         )
+        .add_systems(
+            Update,
+            (
+                reload_config,
+                load_input_settings.run_if(resource_changed::<Config>)
+            )
+            .chain()
+            .run_if(in_state(GameState::Game))
+        )
+        .add_systems(
+            Update,
+            reload_gamebox.run_if(in_state(GameState::Game))
+        )
+        .add_systems(
+            Update,
+            (
+                rescale_reloaded_layouts,
+                request_rasters,
+                finish_rasters,
+                fit_vector_sprites
+            )
+            .chain()
+            .after(reload_gamebox)
+            .run_if(in_state(GameState::Game))
+        )
+        .add_systems(
+            Update,
+            (
+                tick_autosave_timer,
+                autosave_edits.run_if(autosave_due)
+            )
+            .chain()
+            .run_if(in_state(GameState::Game))
+        )
+        .add_systems(Last, clear_recovery_on_exit)
         .init_state::<DebugState>()
         .add_systems(
             OnEnter(DebugState::On),
@@ -299,6 +491,20 @@ fn game_plugin(app: &mut App) {
         )
         .add_observer(open_context_menu)
         .add_observer(close_context_menus)
+        .add_observer(mark_autosave_dirty)
+        .add_observer(piece::refresh_pieces)
+        .add_observer(map::refresh_maps)
+        .add_observer(container::refresh_containers)
+        .add_observer(respawn_grids)
+        .add_observer(reattach_pieces)
+        .add_observer(view::handle_pressed)
+//        .add_observer(debug::dump_edits)
+        .add_observer(debug::pick_dbg);
+}
+
+// the edit observers, shared by the game and the headless checker
+fn edit_plugin(app: &mut App) {
+    app
         .add_observer(on_undo)
         .add_observer(on_redo)
         .add_observer(on_redo_all)
@@ -308,6 +514,12 @@ fn game_plugin(app: &mut App) {
         .add_observer(grid::create::on_create)
         .add_observer(grid::create::on_create_undo)
         .add_observer(grid::create::on_create_redo)
+        .add_observer(map::create::on_create)
+        .add_observer(map::create::on_create_undo)
+        .add_observer(map::create::on_create_redo)
+        .add_observer(container::create::on_create)
+        .add_observer(container::create::on_create_undo)
+        .add_observer(container::create::on_create_redo)
         .add_observer(piece::clone::on_clone_undo)
         .add_observer(piece::clone::on_clone_redo)
         .add_observer(piece::create::on_create)
@@ -321,15 +533,14 @@ fn game_plugin(app: &mut App) {
         .add_observer(piece::r#move::on_move_redo)
         .add_observer(piece::rotate::on_rotate_undo)
         .add_observer(piece::rotate::on_rotate_redo)
+        .add_observer(piece::property::on_set_property_undo)
+        .add_observer(piece::property::on_set_property_redo)
         .add_observer(piece::splice::on_splice_undo)
         .add_observer(piece::splice::on_splice_redo)
         .add_observer(on_group_open)
         .add_observer(on_group_close)
         .add_observer(on_group_undo)
-        .add_observer(on_group_redo)
-        .add_observer(view::handle_pressed)
-//        .add_observer(debug::dump_edits)
-        .add_observer(debug::pick_dbg);
+        .add_observer(on_group_redo);
 }
 
 fn display_game(
@@ -370,9 +581,6 @@ Drag lag: im suspecting that the drag event is either being fired less often tha
 TODO: try turning off vsync to fix drag lag
 */
 
-// TODO: load svg
-// TODO: load pdf
-// TODO: load avif
 // TODO: grid
 // TODO: stacking
 
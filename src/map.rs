@@ -0,0 +1,111 @@
+use bevy::{
+    ecs::{
+        change_detection::Res,
+        component::Component,
+        entity::Entity,
+        name::Name,
+        observer::On,
+        prelude::{ChildOf, Commands, Query, With}
+    },
+    math::{Quat, Vec3},
+    picking::Pickable,
+    prelude::{Sprite, trace, Transform, Visibility, warn}
+};
+use tracing::instrument;
+
+use crate::{
+    assets::{GameBoxReloaded, SpriteHandles},
+    drag::handle_drop,
+    gamebox::{GameBox, MapDefinition},
+    object::ObjectId
+};
+
+pub mod create;
+
+#[derive(Clone, Component, Copy, Debug, Default)]
+pub struct Map;
+
+#[derive(Clone, Component, Copy, Debug)]
+pub struct MapTypeId(pub u32);
+
+pub fn map_transform(m: &MapDefinition) -> Transform {
+    use std::f32::consts::PI;
+
+    // maps lie beneath the grids and pieces on them
+    Transform {
+        translation: Vec3::new(m.x, m.y, 0.0),
+        rotation: Quat::from_rotation_z(m.a * PI / 180.0),
+        scale: Vec3::new(m.s, m.s, 1.0)
+    }
+}
+
+fn map_sprite(
+    m: &MapDefinition,
+    sprite_handles: &SpriteHandles
+) -> Option<Sprite>
+{
+    sprite_handles.0.get(&m.image).map(|src| src.sprite())
+}
+
+pub fn spawn_map(
+    oid: u32,
+    m: &MapDefinition,
+    parent: Entity,
+    sprite_handles: &SpriteHandles,
+    commands: &mut Commands
+) -> Option<Entity>
+{
+    let sprite = map_sprite(m, sprite_handles)?;
+    let anchor: bevy::sprite::Anchor = m.anchor.into();
+
+    let id = commands.spawn((
+        Map,
+        ObjectId(oid),
+        MapTypeId(m.id),
+        Name::from(m.name.as_ref()),
+        sprite,
+        anchor,
+        ChildOf(parent),
+        map_transform(m),
+        Pickable::default(),
+        Visibility::Inherited
+    ))
+    .observe(handle_drop)
+    .id();
+
+    Some(id)
+}
+
+#[instrument(skip_all)]
+pub fn refresh_maps(
+    _evt: On<GameBoxReloaded>,
+    query: Query<(Entity, &ObjectId, &MapTypeId), With<Map>>,
+    gamebox: Res<GameBox>,
+    sprite_handles: Res<SpriteHandles>,
+    mut commands: Commands
+)
+{
+    trace!("");
+
+    for (entity, oid, tid) in query {
+        let Some(m) = gamebox.map.get(&tid.0) else {
+            warn!("map type {} was removed; keeping map {}", tid.0, oid.0);
+            continue;
+        };
+
+        let Some(sprite) = map_sprite(m, &sprite_handles) else {
+            warn!("map type {} has no image; keeping map {}", tid.0, oid.0);
+            continue;
+        };
+
+        // the map is updated in place so that what is on it stays put
+        let anchor: bevy::sprite::Anchor = m.anchor.into();
+
+        commands.entity(entity).insert((
+            Name::from(m.name.as_ref()),
+            sprite,
+            anchor,
+            map_transform(m)
+        ));
+    }
+}
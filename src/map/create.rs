@@ -0,0 +1,122 @@
+use bevy::{
+    ecs::{
+        change_detection::{Res, ResMut},
+        component::Component,
+        error::Result,
+        event::{EntityEvent, Event},
+        observer::On,
+        prelude::{Commands, Entity, Query}
+    },
+    prelude::trace
+};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::{
+    assets::SpriteHandles,
+    edittype::EditType,
+    gamebox::GameBox,
+    log::{EditIndex, Edits, handle_do},
+    map::spawn_map,
+    object::{NextObjectId, ObjectId, ObjectIdMap}
+};
+
+#[derive(Clone, Event)]
+pub struct DoCreateEvent {
+    pub type_id: u32,
+    pub parent: Entity
+}
+
+#[derive(EntityEvent)]
+pub struct UndoCreateEvent {
+    pub entity: Entity
+}
+
+#[derive(EntityEvent)]
+pub struct RedoCreateEvent {
+    pub entity: Entity
+}
+
+#[derive(Component, Debug, Deserialize, Serialize)]
+#[serde(rename = "create_map", tag = "type")]
+pub struct CreateEdit {
+    pub object_id: u32,
+    pub type_id: u32,
+    pub parent_id: u32
+}
+
+#[instrument(skip_all)]
+pub fn on_create(
+    evt: On<DoCreateEvent>,
+    gamebox: Res<GameBox>,
+    mut next_object_id: ResMut<NextObjectId>,
+    parent_query: Query<&ObjectId>,
+    edit_query: Query<(Entity, &mut Edits, &mut EditIndex)>,
+    commands: Commands
+) -> Result
+{
+    trace!("");
+
+    if !gamebox.map.contains_key(&evt.type_id) {
+        return Err("unknown map type".into());
+    }
+
+    let object_id = next_object_id.0;
+    next_object_id.0 += 1;
+
+    let parent_id = parent_query.get(evt.parent)?;
+
+    handle_do(
+        edit_query,
+        EditType::CreateMap,
+        CreateEdit {
+            object_id,
+            type_id: evt.type_id,
+            parent_id: parent_id.0
+        },
+        commands
+    )
+}
+
+#[instrument(skip_all)]
+pub fn on_create_undo(
+    evt: On<UndoCreateEvent>,
+    edit: Query<&CreateEdit>,
+    objmap: Res<ObjectIdMap>,
+    mut commands: Commands
+) -> Result
+{
+// TODO: the edit not existing should be impossible, maybe we should panic?
+    // get the edit
+    let Ok(cr) = edit.get(evt.entity) else { return Ok(()); };
+    // get the entity being edited
+    let entity = objmap.get(cr.object_id)?;
+    // apply the change
+    commands.entity(entity).despawn();
+    Ok(())
+}
+
+#[instrument(skip_all)]
+pub fn on_create_redo(
+    evt: On<RedoCreateEvent>,
+    edit: Query<&CreateEdit>,
+    gamebox: Res<GameBox>,
+    sprite_handles: Res<SpriteHandles>,
+    objmap: Res<ObjectIdMap>,
+    mut commands: Commands
+) -> Result
+{
+    // get the edit
+    let Ok(cr) = edit.get(evt.entity) else { return Ok(()); };
+
+    // get the parent
+    let parent = objmap.get(cr.parent_id)?;
+
+    // apply the change
+    let m = &gamebox.map[&cr.type_id];
+
+    spawn_map(cr.object_id, m, parent, &sprite_handles, &mut commands)
+        .ok_or("map image has not been loaded")?;
+
+    Ok(())
+}
@@ -19,6 +19,16 @@ pub struct ObjectId(pub u32);
 #[derive(Default, Resource)]
 pub struct ObjectIdMap(pub HashMap<u32, Entity>);
 
+#[derive(Debug, thiserror::Error)]
+#[error("no object has id {0}")]
+pub struct UnknownObjectIdError(pub u32);
+
+impl ObjectIdMap {
+    pub fn get(&self, oid: u32) -> Result<Entity, UnknownObjectIdError> {
+        self.0.get(&oid).copied().ok_or(UnknownObjectIdError(oid))
+    }
+}
+
 #[derive(Default, Resource)]
 pub struct NextObjectId(pub u32);
 
@@ -5,22 +5,28 @@ use bevy::{
         event::EntityEvent,
         name::Name,
         observer::On,
-        prelude::{ChildOf, Commands, EntityCommands, Query}
+        prelude::{ChildOf, Children, Commands, EntityCommands, Query, Res, With, Without}
     },
-    math::{Quat, Vec3},
+    color::Srgba,
+    math::{Quat, Vec2, Vec3},
     picking::Pickable,
-    prelude::{Color, debug, Sprite, trace, Transform, Visibility}
+    prelude::{Color, debug, Sprite, trace, Transform, Visibility, warn}
+};
+use std::{
+    collections::{HashMap, HashSet},
+    mem
 };
 use tracing::instrument;
 
 use crate::{
     actionfunc::{ActionFunc, add_action_observers},
-    assets::{ImageSource, SpriteHandles},
+    assets::{GameBoxReloaded, ImageSource, SpriteHandles},
     drag::{Draggable, handle_drop, on_piece_drag_start, on_piece_drag, on_piece_drag_end},
-    gamebox::{Anchor, PieceType},
+    gamebox::{Anchor, Condition, FaceDefinition, GameBox, LayerDefinition, PieceType, PropertyValue},
     keys::KeyBinding,
     object::ObjectId,
     piece::{
+        flip::FaceBottom,
         r#move::on_move,
         splice::on_splice,
     },
@@ -33,7 +39,9 @@ pub mod clone;
 pub mod create;
 pub mod delete;
 pub mod flip;
+pub mod label;
 pub mod r#move;
+pub mod property;
 pub mod rotate;
 pub mod splice;
 
@@ -46,9 +54,41 @@ pub struct PieceTypeId(pub u32);
 #[derive(Clone, Component, Copy, Debug, Eq, PartialEq)]
 pub struct StackingGroup(pub u32);
 
+// an image drawn over the bottom of a face
+#[derive(Clone, Debug)]
+pub struct Layer {
+    pub image: ImageSource,
+    pub offset: Vec2,
+    pub tint: Color,
+    pub when: Option<Condition>
+}
+
+// the bottom image is the piece's own sprite; the layers go above it
+#[derive(Clone, Debug)]
+pub struct Face {
+    pub base: ImageSource,
+    // a bottom layer with an offset, tint, or condition is drawn like the
+    // layers are, over a clear sprite which still gives the piece its shape
+    pub bottom: Option<Layer>,
+    pub layers: Vec<Layer>
+}
+
+const SELECTED_COLOR: Color = Color::hsl(0.0, 0.9, 0.7);
+
+// the colors of a piece's sprite and of its bottom layer, if it has one;
+// selection colors whichever of them shows the bottom image
+pub fn bottom_colors(face: &Face, selected: bool) -> (Color, Color) {
+    match (&face.bottom, selected) {
+        (None, false) => (Color::WHITE, Color::WHITE),
+        (None, true) => (SELECTED_COLOR, SELECTED_COLOR),
+        (Some(b), false) => (Color::NONE, b.tint),
+        (Some(_), true) => (Color::NONE, SELECTED_COLOR)
+    }
+}
+
 // TODO: should this reference a piece type?
 #[derive(Clone, Component, Debug, Default)]
-pub struct Faces(pub Vec<ImageSource>);
+pub struct Faces(pub Vec<Face>);
 
 // TODO: should this be a cyclic iterator?
 #[derive(Clone, Component, Copy, Debug, Default)]
@@ -75,6 +115,10 @@ pub struct Below(Vec<Entity>);
 #[derive(Clone, Component, Copy, Debug)]
 pub struct Location(pub Vec3);
 
+// per-instance values, starting from those of the piece type
+#[derive(Clone, Component, Debug, Default)]
+pub struct Properties(pub HashMap<String, PropertyValue>);
+
 #[derive(Clone, Component, Copy, Debug)]
 pub struct Angle(pub f32);
 
@@ -89,13 +133,97 @@ pub fn add_draggable_observers(ec: &mut EntityCommands) {
 
 pub fn add_selectable_observers(ec: &mut EntityCommands) {
     ec
-        .observe(recolor_on::<SelectEvent>(Color::hsl(0.0, 0.9, 0.7)))
-        .observe(recolor_on::<DeselectEvent>(Color::WHITE))
+        .observe(recolor_on::<SelectEvent>(true))
+        .observe(recolor_on::<DeselectEvent>(false))
         .observe(on_selection)
         .observe(on_deselection)
         .observe(handle_piece_pressed);
 }
 
+fn piece_layer(
+    pid: u32,
+    l: &LayerDefinition,
+    sprite_handles: &SpriteHandles
+) -> Option<Layer>
+{
+    let Some(image) = sprite_handles.0.get(&l.image) else {
+        warn!("piece type {pid}: layer image {:?} is missing; leaving it out", l.image);
+        return None;
+    };
+
+    Some(Layer {
+        image: image.clone(),
+        offset: Vec2::new(l.x, l.y),
+        tint: l.tint.as_deref()
+            .and_then(|t| Srgba::hex(t).ok())
+            .map_or(Color::WHITE, Color::from),
+        when: l.when.clone()
+    })
+}
+
+fn piece_face(
+    pid: u32,
+    f: &FaceDefinition,
+    sprite_handles: &SpriteHandles
+) -> Option<Face>
+{
+    let (image, bottom, rest) = match f {
+        FaceDefinition::Image(image) => (image, None, &[][..]),
+        FaceDefinition::Layers(layers) => {
+            let (bottom, rest) = layers.split_first()?;
+            (&bottom.image, Some(bottom), rest)
+        }
+    };
+
+    let base = sprite_handles.0.get(image)?.clone();
+
+    let bottom = bottom.filter(|b| !b.is_plain())
+        .and_then(|b| piece_layer(pid, b, sprite_handles));
+
+    let layers = rest.iter()
+        .filter_map(|l| piece_layer(pid, l, sprite_handles))
+        .collect();
+
+    Some(Face {
+        base,
+        bottom,
+        layers
+    })
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("piece type {type_id} has no image for face {face}")]
+pub struct MissingFaceError {
+    pub type_id: u32,
+    pub face: usize
+}
+
+// a face whose image is missing would shift the ones after it, so it
+// spoils them all
+fn piece_faces(
+    pid: u32,
+    p: &PieceType,
+    sprite_handles: &SpriteHandles
+) -> Result<Vec<Face>, MissingFaceError>
+{
+    p.faces.iter()
+        .enumerate()
+        .map(|(face, f)| piece_face(pid, f, sprite_handles)
+            .ok_or(MissingFaceError { type_id: pid, face }))
+        .collect()
+}
+
+fn piece_actions(p: &PieceType) -> Actions {
+    Actions(p.actions.iter()
+        .map(|a| Action {
+            label: a.label.clone(),
+            action: a.action.clone(),
+            key: a.key.clone()
+        })
+        .collect()
+    )
+}
+
 pub fn spawn_piece(
     oid: u32,
     pid: u32,
@@ -107,21 +235,14 @@ pub fn spawn_piece(
     faceup: usize,
     sprite_handles: &SpriteHandles,
     commands: &mut Commands
-)
+) -> Result<Entity, MissingFaceError>
 {
-// FIXME: should fail if we can't get a sprite?
-    let faces = p.faces.iter()
-        .filter_map(|f| sprite_handles.0.get(f))
-        .cloned()
-        .collect::<Vec<_>>();
-
-    let sprite = match &faces[0] {
-        ImageSource::Single(handle) => Sprite::from_image(handle.clone()),
-        ImageSource::Crop { handle, atlas } => Sprite::from_atlas_image(
-            handle.clone(),
-            atlas.clone()
-        )
-    };
+    let faces = piece_faces(pid, p, sprite_handles)?;
+
+    let sprite = faces.get(faceup)
+        .ok_or(MissingFaceError { type_id: pid, face: faceup })?
+        .base
+        .sprite();
 
     use std::f32::consts::PI;
 
@@ -151,14 +272,8 @@ pub fn spawn_piece(
         (
             Faces(faces),
             FaceUp(faceup),
-            Actions(p.actions.iter()
-                .map(|a| Action {
-                    label: a.label.clone(),
-                    action: a.action,
-                    key: a.key.clone()
-                })
-                .collect::<Vec<_>>()
-            ),
+            Properties(p.properties.clone()),
+            piece_actions(p),
             Pickable::default(),
             Visibility::Inherited
         )
@@ -182,16 +297,92 @@ pub fn spawn_piece(
         add_draggable_observers(&mut ec);
     }
 
-    add_action_observers(p.actions.iter().map(|a| a.action), &mut ec);
+    add_action_observers(p.actions.iter().map(|a| a.action.clone()), &mut ec);
+
+    Ok(ec.id())
+}
+
+#[instrument(skip_all)]
+pub fn refresh_pieces(
+    _evt: On<GameBoxReloaded>,
+    mut query: Query<(Entity, &PieceTypeId, &mut Name, &mut StackingGroup, &mut Faces, &mut FaceUp, &mut Properties, &mut Actions), With<Piece>>,
+    gamebox: Res<GameBox>,
+    sprite_handles: Res<SpriteHandles>,
+    mut commands: Commands
+)
+{
+    trace!("");
+
+    for (entity, pid, mut name, mut sg, mut faces, mut up, mut props, mut actions) in query.iter_mut() {
+        let Some(p) = gamebox.piece.get(&pid.0) else {
+            warn!("piece type {} was removed; keeping {}", pid.0, name.as_str());
+            continue;
+        };
+
+        let new_faces = match piece_faces(pid.0, p, &sprite_handles) {
+            Ok(f) if !f.is_empty() => f,
+            Ok(_) => {
+                warn!("piece type {} has no faces; keeping {}", pid.0, name.as_str());
+                continue;
+            },
+            Err(e) => {
+                warn!("{e}; keeping {}", name.as_str());
+                continue;
+            }
+        };
+
+        name.set(p.name.clone());
+        sg.0 = p.stacking_group;
+
+        // keep the same side up where possible
+        faces.0 = new_faces;
+        if up.0 >= faces.0.len() {
+            up.0 = 0;
+        }
+        // redraw the sprite even if the index did not change
+        up.set_changed();
+
+        // values set during play win over those of the type
+        for (k, v) in &p.properties {
+            props.0.entry(k.clone()).or_insert_with(|| v.clone());
+        }
+
+        // observe only kinds of actions the piece did not have before
+        let old = actions.0.iter()
+            .map(|a| mem::discriminant(&a.action))
+            .collect::<HashSet<_>>();
+
+        *actions = piece_actions(p);
+
+        add_action_observers(
+            actions.0.iter()
+                .map(|a| a.action.clone())
+                .filter(|a| !old.contains(&mem::discriminant(a))),
+            &mut commands.entity(entity)
+        );
+    }
 }
 
 fn recolor_on<E: EntityEvent>(
-    color: Color
-) -> impl Fn(On<E>, Query<&mut Sprite>)
+    selected: bool
+) -> impl Fn(
+    On<E>,
+    Query<(&mut Sprite, &Faces, &FaceUp, Option<&Children>), Without<FaceBottom>>,
+    Query<&mut Sprite, With<FaceBottom>>
+)
 {
-    move |ev, mut sprites| {
-        if let Ok(mut sprite) = sprites.get_mut(ev.event().event_target()) {
-            sprite.color = color;
+    move |ev, mut pieces, mut bottoms| {
+        let Ok((mut sprite, faces, up, children)) = pieces.get_mut(ev.event().event_target()) else {
+            return;
+        };
+
+        let (color, bottom_color) = bottom_colors(&faces.0[up.0], selected);
+        sprite.color = color;
+
+        for c in children.iter().flat_map(|c| c.iter()) {
+            if let Ok(mut bottom) = bottoms.get_mut(c) {
+                bottom.color = bottom_color;
+            }
         }
     }
 }
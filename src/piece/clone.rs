@@ -63,7 +63,7 @@ fn do_clone(
         add_draggable_observers(&mut ec);
     }
 
-    add_action_observers(actions.0.iter().map(|a| a.action), &mut ec);
+    add_action_observers(actions.0.iter().map(|a| a.action.clone()), &mut ec);
 }
 
 #[derive(Component, Debug, Deserialize, Serialize)]
@@ -109,7 +109,7 @@ pub fn on_clone_undo(
     // get the edit
     let Ok(cl) = edit.get(evt.entity) else { return Ok(()); };
     // get the entity being edited
-    let entity = *objmap.0.get(&cl.object_id).unwrap();
+    let entity = objmap.get(cl.object_id)?;
     // apply the change
     commands.entity(entity).despawn();
     Ok(())
@@ -127,7 +127,7 @@ pub fn on_clone_redo(
     // get the edit
     let Ok(cl) = edit.get(evt.entity) else { return Ok(()); };
     // get the source entity
-    let entity = *objmap.0.get(&cl.source_id).unwrap();
+    let entity = objmap.get(cl.source_id)?;
     // get the components of the source entity
     let (selectable, draggable, actions) = query.get(entity)?;
 
@@ -52,7 +52,9 @@ pub struct CreateEdit {
     #[serde(default)]
     pub angle: f32,
     #[serde(default)]
-    pub anchor: Anchor
+    pub anchor: Anchor,
+    #[serde(default)]
+    pub face: usize
 }
 
 #[instrument(skip_all)]
@@ -80,7 +82,8 @@ pub fn on_create(
             parent_id: parent_id.0,
             dst: evt.dst,
             angle: evt.angle,
-            anchor: evt.anchor
+            anchor: evt.anchor,
+            face: 0
         },
         commands
     )
@@ -97,7 +100,7 @@ pub fn on_create_undo(
     // get the edit
     let Ok(cr) = edit.get(evt.entity) else { return Ok(()); };
     // get the entity being edited
-    let entity = *objmap.0.get(&cr.object_id).unwrap();
+    let entity = objmap.get(cr.object_id)?;
     // apply the change
     commands.entity(entity).despawn();
     Ok(())
@@ -119,7 +122,7 @@ pub fn on_create_redo(
     // get the edit
     let Ok(cr) = edit.get(evt.entity) else { return Ok(()); };
     // get the parent entity
-    let parent = *objmap.0.get(&cr.parent_id).unwrap();
+    let parent = objmap.get(cr.parent_id)?;
 
     // update max z
     let root = root_query.root_ancestor(parent);
@@ -141,9 +144,9 @@ pub fn on_create_redo(
         cr.dst,
         cr.angle,
         cr.anchor,
-        0,
+        cr.face,
         &sprite_handles,
         &mut commands
-    );
+    )?;
     Ok(())
 }
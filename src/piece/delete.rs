@@ -12,15 +12,16 @@ use bevy::{
     sprite::Anchor
 };
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use tracing::instrument;
 
 use crate::{
     assets::SpriteHandles,
     edittype::EditType,
-    gamebox::{self, GameBox},
+    gamebox::{self, GameBox, PropertyValue},
     log::{EditIndex, Edits, handle_do},
     object::{ObjectId, ObjectIdMap},
-    piece::{FaceUp, PieceTypeId, spawn_piece}
+    piece::{FaceUp, PieceTypeId, Properties, spawn_piece}
 };
 
 #[derive(Clone, EntityEvent)]
@@ -55,7 +56,10 @@ pub struct DeleteEdit {
     pub location: Vec3,
     pub angle: f32,
     pub anchor: gamebox::Anchor,
-    pub faceup: usize
+    pub faceup: usize,
+    // absent from logs made before pieces had properties
+    #[serde(default)]
+    pub properties: Option<HashMap<String, PropertyValue>>
 }
 
 // TODO: should pieces have an id for their piece type?
@@ -64,7 +68,7 @@ pub struct DeleteEdit {
 #[instrument(skip_all)]
 pub fn on_delete(
     evt: On<DoDeleteEvent>,
-    piece_query: Query<(&ObjectId, &PieceTypeId, &ChildOf, &Transform, &Anchor, &FaceUp)>,
+    piece_query: Query<(&ObjectId, &PieceTypeId, &ChildOf, &Transform, &Anchor, &FaceUp, &Properties)>,
     parent_query: Query<&ObjectId>,
     edit_query: Query<(Entity, &mut Edits, &mut EditIndex)>,
     commands: Commands
@@ -73,7 +77,7 @@ pub fn on_delete(
     trace!("");
 
     let entity = evt.event().event_target();
-    let (object_id, type_id, parent, t, anchor, faceup) = piece_query.get(entity)?;
+    let (object_id, type_id, parent, t, anchor, faceup, props) = piece_query.get(entity)?;
     let parent_id = parent_query.get(parent.0)?;
 
     handle_do(
@@ -86,7 +90,8 @@ pub fn on_delete(
             location: t.translation,
             angle: t.rotation.to_axis_angle().1,
             anchor: (*anchor).into(),
-            faceup: faceup.0
+            faceup: faceup.0,
+            properties: Some(props.0.clone())
         },
         commands
     )
@@ -105,10 +110,10 @@ pub fn on_delete_undo(
     // get the edit
     let Ok(del) = edit.get(evt.entity) else { return Ok(()); };
     // get the parent entity
-    let parent = *objmap.0.get(&del.parent_id).unwrap();
+    let parent = objmap.get(del.parent_id)?;
 
     // apply the change
-    spawn_piece(
+    let entity = spawn_piece(
         del.object_id,
         del.type_id,
         &gamebox.piece[&del.type_id],
@@ -119,7 +124,11 @@ pub fn on_delete_undo(
         del.faceup,
         &sprite_handles,
         &mut commands
-    );
+    )?;
+
+    if let Some(props) = &del.properties {
+        commands.entity(entity).insert(Properties(props.clone()));
+    }
 
     Ok(())
 }
@@ -135,7 +144,7 @@ pub fn on_delete_redo(
     // get the edit
     let Ok(del) = edit.get(evt.entity) else { return Ok(()); };
     // get the source entity
-    let entity = *objmap.0.get(&del.object_id).unwrap();
+    let entity = objmap.get(del.object_id)?;
     // apply the change
     do_delete(entity, &mut commands);
     Ok(())
@@ -5,9 +5,11 @@ use bevy::{
         error::Result,
         event::EntityEvent,
         observer::On,
-        prelude::{Changed, Commands, Query}
+        prelude::{Changed, ChildOf, Children, Commands, Has, Or, Query, With}
     },
-    prelude::{Entity, Sprite, trace}
+    picking::Pickable,
+    prelude::{Entity, Sprite, trace, Transform},
+    sprite::Anchor
 };
 use serde::{Deserialize, Serialize};
 use tracing::instrument;
@@ -17,9 +19,20 @@ use crate::{
     edittype::EditType,
     log::{EditIndex, Edits, handle_do},
     object::{ObjectId, ObjectIdMap},
-    piece::{Faces, FaceUp}
+    piece::{Face, Faces, FaceUp, Properties, bottom_colors},
+    select::Selected
 };
 
+// layers stack between their piece and its labels
+const LAYER_Z_STEP: f32 = 0.01;
+
+#[derive(Clone, Component, Copy, Debug)]
+pub struct FaceLayer;
+
+// the layer which draws the bottom of a face in place of the piece's sprite
+#[derive(Clone, Component, Copy, Debug)]
+pub struct FaceBottom;
+
 #[derive(Clone, EntityEvent)]
 pub struct DoFlipEvent {
     pub entity: Entity,
@@ -37,12 +50,17 @@ pub struct RedoFlipEvent {
 }
 
 fn set_face(
+    entity: Entity,
     sprite: &mut Sprite,
-    faces: &Faces,
-    up: &FaceUp
+    anchor: Anchor,
+    face: &Face,
+    props: &Properties,
+    selected: bool,
+    old_layers: impl Iterator<Item = Entity>,
+    commands: &mut Commands
 )
 {
-    match &faces.0[up.0] {
+    match &face.base {
         ImageSource::Single(handle) => {
             sprite.image = handle.clone();
             sprite.texture_atlas = None;
@@ -52,6 +70,38 @@ fn set_face(
             sprite.texture_atlas = Some(atlas.clone());
         }
     }
+
+    let (color, bottom_color) = bottom_colors(face, selected);
+    sprite.color = color;
+
+    // replace the layers of the old face with those of the new one
+    old_layers.for_each(|l| commands.entity(l).despawn());
+
+    let shown = face.bottom.iter()
+        .map(|l| (l, bottom_color, true))
+        .chain(face.layers.iter().map(|l| (l, l.tint, false)))
+        .filter(|(l, _, _)| l.when.as_ref().is_none_or(|w| w.holds(&props.0)));
+
+    for (i, (l, color, is_bottom)) in shown.enumerate() {
+        let mut layer_sprite = l.image.sprite();
+        layer_sprite.color = color;
+
+        let mut ec = commands.spawn((
+            FaceLayer,
+            layer_sprite,
+            anchor,
+            Transform::from_translation(
+                l.offset.extend(LAYER_Z_STEP * (i + 1) as f32)
+            ),
+            // clicks go to the piece
+            Pickable::IGNORE,
+            ChildOf(entity)
+        ));
+
+        if is_bottom {
+            ec.insert(FaceBottom);
+        }
+    }
 }
 
 #[derive(Component, Debug, Deserialize, Serialize)]
@@ -92,7 +142,7 @@ fn apply_flip<const DO: bool>(
     // get the edit
     let Ok(flip) = edit.get(event_target) else { return Ok(()); };
     // get the entity being edited
-    let entity = *objmap.0.get(&flip.object_id).unwrap();
+    let entity = objmap.get(flip.object_id)?;
     // get the components of the entity being edited
     let (mut up, faces) = query.get_mut(entity)?;
     // apply the change to the entity
@@ -126,10 +176,25 @@ pub fn on_flip_redo(
 
 #[instrument(skip_all)]
 pub fn on_face_change(
-    mut query: Query<(&mut Sprite, &Faces, &FaceUp), Changed<FaceUp>>
+    mut query: Query<(Entity, &mut Sprite, &Anchor, &Faces, &FaceUp, &Properties, Has<Selected>, Option<&Children>), Or<(Changed<FaceUp>, Changed<Properties>)>>,
+    layer_query: Query<(), With<FaceLayer>>,
+    mut commands: Commands
 )
 {
-    for (mut sprite, faces, up) in query.iter_mut() {
-        set_face(&mut sprite, faces, up);
+    for (entity, mut sprite, anchor, faces, up, props, selected, children) in query.iter_mut() {
+        let old_layers = children.iter()
+            .flat_map(|c| c.iter())
+            .filter(|c| layer_query.contains(*c));
+
+        set_face(
+            entity,
+            &mut sprite,
+            *anchor,
+            &faces.0[up.0],
+            props,
+            selected,
+            old_layers,
+            &mut commands
+        );
     }
 }
@@ -0,0 +1,75 @@
+use bevy::{
+    color::Srgba,
+    ecs::{
+        change_detection::Res,
+        component::Component,
+        entity::Entity,
+        prelude::{Changed, ChildOf, Children, Commands, Or, Query, With}
+    },
+    picking::Pickable,
+    prelude::{Color, FontSize, Text2d, TextColor, TextFont, trace, Transform}
+};
+use tracing::instrument;
+
+use crate::{
+    assets::FontHandles,
+    gamebox::{GameBox, LabelDefinition},
+    piece::{FaceUp, Piece, PieceTypeId, Properties}
+};
+
+// labels sit just above their piece, but below the next piece in a stack
+const LABEL_Z: f32 = 0.5;
+
+#[derive(Clone, Component, Copy, Debug)]
+pub struct PieceLabel;
+
+fn label_font(l: &LabelDefinition, font_handles: &FontHandles) -> TextFont {
+    let mut font = TextFont {
+        font_size: FontSize::Px(l.size),
+        ..Default::default()
+    };
+
+    if let Some(h) = l.font.as_ref().and_then(|f| font_handles.0.get(f)) {
+        font.font = h.clone().into();
+    }
+
+    font
+}
+
+// redraws the labels of pieces whose properties or face changed
+#[instrument(skip_all)]
+pub fn update_labels(
+    query: Query<(Entity, &PieceTypeId, &Properties, &FaceUp, Option<&Children>), (With<Piece>, Or<(Changed<Properties>, Changed<FaceUp>)>)>,
+    label_query: Query<(), With<PieceLabel>>,
+    gamebox: Res<GameBox>,
+    font_handles: Res<FontHandles>,
+    mut commands: Commands
+)
+{
+    trace!("");
+
+    for (entity, pid, props, up, children) in &query {
+        children.iter()
+            .flat_map(|c| c.iter())
+            .filter(|c| label_query.contains(*c))
+            .for_each(|c| commands.entity(c).despawn());
+
+        let Some(p) = gamebox.piece.get(&pid.0) else { continue; };
+
+        for l in p.labels.iter().filter(|l| l.shown_on(up.0)) {
+            let color = Srgba::hex(&l.color)
+                .map_or(Color::BLACK, Color::from);
+
+            commands.spawn((
+                PieceLabel,
+                Text2d::new(l.format(&props.0)),
+                label_font(l, &font_handles),
+                TextColor(color),
+                Transform::from_xyz(l.x, l.y, LABEL_Z),
+                // clicks go to the piece
+                Pickable::IGNORE,
+                ChildOf(entity)
+            ));
+        }
+    }
+}
@@ -92,14 +92,14 @@ fn apply_move<const DO: bool>(
     let Ok(mov) = edit.get(entity) else { return Ok(()); };
 
     // get the entity being edited
-    let entity = *objmap.0.get(&mov.object_id).unwrap();
+    let entity = objmap.get(mov.object_id)?;
 
     let mut mov_loc = mov_query.get_mut(entity)?;
 
     if mov.src_parent_id != mov.dst_parent_id {
         let new_parent_id = if DO { mov.dst_parent_id } else { mov.src_parent_id };
 
-        let new_parent = *objmap.0.get(&new_parent_id).unwrap();
+        let new_parent = objmap.get(new_parent_id)?;
 
         // reparent the child
         commands.entity(new_parent)
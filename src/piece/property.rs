@@ -0,0 +1,164 @@
+use bevy::{
+    ecs::{
+        change_detection::Res,
+        component::Component,
+        error::Result,
+        event::EntityEvent,
+        observer::On,
+        prelude::{Commands, Query}
+    },
+    prelude::{Entity, trace}
+};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::{
+    edittype::EditType,
+    gamebox::PropertyValue,
+    log::{EditIndex, Edits, handle_do},
+    object::{ObjectId, ObjectIdMap},
+    piece::Properties
+};
+
+#[derive(Clone, EntityEvent)]
+pub struct DoAdjustEvent {
+    pub entity: Entity,
+    pub key: String,
+    pub delta: i64
+}
+
+#[derive(Clone, EntityEvent)]
+pub struct DoToggleEvent {
+    pub entity: Entity,
+    pub key: String
+}
+
+#[derive(EntityEvent)]
+pub struct UndoSetPropertyEvent {
+    pub entity: Entity
+}
+
+#[derive(EntityEvent)]
+pub struct RedoSetPropertyEvent {
+    pub entity: Entity
+}
+
+// old and new are None where the piece lacks the property
+#[derive(Component, Debug, Deserialize, Serialize)]
+#[serde(rename = "set_property", tag = "type")]
+pub struct SetPropertyEdit {
+    pub object_id: u32,
+    pub key: String,
+    pub old: Option<PropertyValue>,
+    pub new: Option<PropertyValue>
+}
+
+#[instrument(skip_all)]
+pub fn on_adjust(
+    evt: On<DoAdjustEvent>,
+    piece_query: Query<(&ObjectId, &Properties)>,
+    edit_query: Query<(Entity, &mut Edits, &mut EditIndex)>,
+    commands: Commands
+) -> Result
+{
+    trace!("");
+
+    let entity = evt.event().event_target();
+    let (object_id, props) = piece_query.get(entity)?;
+
+    let old = props.0.get(&evt.key);
+
+    let Some(PropertyValue::Int(i)) = old else {
+        return Err(format!("property {:?} is not an integer", evt.key).into());
+    };
+
+    handle_do(
+        edit_query,
+        EditType::SetProperty,
+        SetPropertyEdit {
+            object_id: object_id.0,
+            key: evt.key.clone(),
+            old: old.cloned(),
+            new: Some(PropertyValue::Int(
+                i.checked_add(evt.delta)
+                    .ok_or_else(|| format!("property {:?} would overflow", evt.key))?
+            ))
+        },
+        commands
+    )
+}
+
+#[instrument(skip_all)]
+pub fn on_toggle(
+    evt: On<DoToggleEvent>,
+    piece_query: Query<(&ObjectId, &Properties)>,
+    edit_query: Query<(Entity, &mut Edits, &mut EditIndex)>,
+    commands: Commands
+) -> Result
+{
+    trace!("");
+
+    let entity = evt.event().event_target();
+    let (object_id, props) = piece_query.get(entity)?;
+
+    let old = props.0.get(&evt.key);
+
+    let Some(PropertyValue::Bool(b)) = old else {
+        return Err(format!("property {:?} is not a boolean", evt.key).into());
+    };
+
+    handle_do(
+        edit_query,
+        EditType::SetProperty,
+        SetPropertyEdit {
+            object_id: object_id.0,
+            key: evt.key.clone(),
+            old: old.cloned(),
+            new: Some(PropertyValue::Bool(!b))
+        },
+        commands
+    )
+}
+
+fn apply_set_property<const DO: bool>(
+    event_target: Entity,
+    edit: Query<&SetPropertyEdit>,
+    objmap: Res<ObjectIdMap>,
+    mut query: Query<&mut Properties>
+) -> Result
+{
+    // get the edit
+    let Ok(set) = edit.get(event_target) else { return Ok(()); };
+    // get the entity being edited
+    let entity = objmap.get(set.object_id)?;
+    // get the components of the entity being edited
+    let mut props = query.get_mut(entity)?;
+    // apply the change to the entity
+    match if DO { &set.new } else { &set.old } {
+        Some(v) => props.0.insert(set.key.clone(), v.clone()),
+        None => props.0.remove(&set.key)
+    };
+    Ok(())
+}
+
+#[instrument(skip_all)]
+pub fn on_set_property_undo(
+    evt: On<UndoSetPropertyEvent>,
+    edit: Query<&SetPropertyEdit>,
+    objmap: Res<ObjectIdMap>,
+    query: Query<&mut Properties>
+) -> Result
+{
+    apply_set_property::<false>(evt.entity, edit, objmap, query)
+}
+
+#[instrument(skip_all)]
+pub fn on_set_property_redo(
+    evt: On<RedoSetPropertyEvent>,
+    edit: Query<&SetPropertyEdit>,
+    objmap: Res<ObjectIdMap>,
+    query: Query<&mut Properties>
+) -> Result
+{
+    apply_set_property::<true>(evt.entity, edit, objmap, query)
+}
@@ -73,7 +73,7 @@ fn apply_rotate<const DO: bool>(
     // get the edit
     let Ok(rot) = edit.get(event_target) else { return Ok(()); };
     // get the entity being edited
-    let entity = *objmap.0.get(&rot.object_id).unwrap();
+    let entity = objmap.get(rot.object_id)?;
     // get the components of the entity being edited
     let mut a = query.get_mut(entity)?;
 
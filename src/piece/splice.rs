@@ -108,7 +108,7 @@ fn apply_splice<const DO: bool>(
     let Ok(spl) = edit.get(entity) else { return Ok(()); };
 
     // get the entity being edited
-    let entity = *objmap.0.get(&spl.object_id).unwrap();
+    let entity = objmap.get(spl.object_id)?;
 
     let (old_parent_id, old_child_id, new_parent_id, new_child_id) = if DO {
         (
@@ -129,8 +129,8 @@ fn apply_splice<const DO: bool>(
 
     // splice out the entity
     if let Some(old_child_id) = old_child_id {
-        let old_parent = *objmap.0.get(&old_parent_id).unwrap();
-        let old_child = *objmap.0.get(&old_child_id).unwrap();
+        let old_parent = objmap.get(old_parent_id)?;
+        let old_child = objmap.get(old_child_id)?;
 
         commands.entity(old_parent)
             .add_one_related::<Above>(old_child);
@@ -140,13 +140,13 @@ fn apply_splice<const DO: bool>(
     }
 
     // splice in the entity
-    let new_parent = *objmap.0.get(&new_parent_id).unwrap();
+    let new_parent = objmap.get(new_parent_id)?;
 
     commands.entity(new_parent)
         .add_one_related::<Above>(entity);
 
     if let Some(new_child_id) = new_child_id {
-        let new_child = *objmap.0.get(&new_child_id).unwrap();
+        let new_child = objmap.get(new_child_id)?;
 
         commands.entity(entity)
             .add_one_related::<Above>(new_child);
@@ -0,0 +1,248 @@
+use bevy::{
+    asset::{Assets, AssetId, Handle, RenderAssetUsages},
+    camera::Camera,
+    ecs::{
+        change_detection::{DetectChanges, DetectChangesMut, Res, ResMut},
+        error::Result,
+        prelude::{Query, Resource, With}
+    },
+    image::Image,
+    math::URect,
+    prelude::{Projection, Sprite, TextureAtlasLayout, warn},
+    tasks::{AsyncComputeTaskPool, Task, block_on, futures_lite::future}
+};
+use image::DynamicImage;
+use std::{
+    collections::HashMap,
+    path::Path
+};
+
+use crate::{
+    archive::{ArchiveError, GameBoxFiles},
+    assets::LoadingHandles,
+    loader::{DecodeError, RasterSettings, decode, split_label},
+    util::AsOrthographicProjection
+};
+
+// vector images are drawn at most this many times their gamebox dpi
+const MAX_RASTER_SCALE: u32 = 4;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RasterError {
+    #[error("{0}")]
+    Read(#[from] ArchiveError),
+    #[error("{0}")]
+    Decode(#[from] DecodeError)
+}
+
+// a vector image, and the atlases cut from it at its gamebox dpi
+pub struct VectorImage {
+    pub handle: Handle<Image>,
+    pub file: String,
+    pub dpi: f32,
+    // PDF pages are drawn only by raster tasks, never by an asset loader
+    pub pdf: bool,
+    pub layouts: Vec<(Handle<TextureAtlasLayout>, TextureAtlasLayout)>
+}
+
+#[derive(Default, Resource)]
+pub struct VectorImages(pub HashMap<AssetId<Image>, VectorImage>);
+
+// how many times its gamebox dpi each redrawn vector image is drawn at
+#[derive(Default, Resource)]
+pub struct RasterScales(pub HashMap<AssetId<Image>, u32>);
+
+#[derive(Default, Resource)]
+pub struct RasterTasks(Vec<(AssetId<Image>, u32, Task<Result<DynamicImage, RasterError>>)>);
+
+fn scaled_layout(base: &TextureAtlasLayout, s: u32) -> TextureAtlasLayout {
+    TextureAtlasLayout {
+        size: base.size * s,
+        textures: base.textures.iter()
+            .map(|r| URect::from_corners(r.min * s, r.max * s))
+            .collect()
+    }
+}
+
+fn rescale_layouts(
+    v: &VectorImage,
+    s: u32,
+    layouts: &mut Assets<TextureAtlasLayout>
+)
+{
+    for (handle, base) in &v.layouts {
+        if let Some(layout) = layouts.get_mut(handle).as_deref_mut() {
+            *layout = scaled_layout(base, s);
+        }
+    }
+}
+
+// the scale at which vector images stay sharp at the camera's zoom
+fn wanted_scale(projection: &Projection) -> Result<u32> {
+    let zoom = 1.0 / projection.as_ortho()?.scale;
+    Ok((zoom.ceil().max(1.0) as u32)
+        .next_power_of_two()
+        .min(MAX_RASTER_SCALE))
+}
+
+fn rasterize(
+    files: &GameBoxFiles,
+    file: &str,
+    dpi: f32
+) -> Result<DynamicImage, RasterError>
+{
+    let (f, label) = split_label(file);
+    let path = Path::new(f);
+    let bytes = files.read(path)?;
+    Ok(decode(bytes, path, label, &RasterSettings { dpi })?)
+}
+
+fn spawn_raster(
+    files: &GameBoxFiles,
+    v: &VectorImage,
+    scale: u32
+) -> Task<Result<DynamicImage, RasterError>>
+{
+    let files = files.clone();
+    let file = v.file.clone();
+    let dpi = v.dpi * scale as f32;
+
+    AsyncComputeTaskPool::get()
+        .spawn(async move { rasterize(&files, &file, dpi) })
+}
+
+// starts drawing the PDF pages which have not been drawn yet
+pub fn start_pdf_rasters(
+    vector_images: &VectorImages,
+    scales: &RasterScales,
+    files: &GameBoxFiles,
+    tasks: &mut RasterTasks
+)
+{
+    for (id, v) in &vector_images.0 {
+        if v.pdf && !scales.0.contains_key(id) {
+            tasks.0.push((*id, 1, spawn_raster(files, v, 1)));
+        }
+    }
+}
+
+// starts redrawing vector images whose scale does not suit the zoom
+pub fn request_rasters(
+    camera_query: Query<&Projection, With<Camera>>,
+    vector_images: Res<VectorImages>,
+    scales: Res<RasterScales>,
+    files: Res<GameBoxFiles>,
+    mut tasks: ResMut<RasterTasks>
+) -> Result
+{
+    let wanted = wanted_scale(camera_query.single()?)?;
+
+    // a task for another scale is no longer wanted; dropping it cancels it
+    tasks.0.retain(|(_, s, _)| *s == wanted);
+
+    for (id, v) in &vector_images.0 {
+        let current = scales.0.get(id).copied().unwrap_or(1);
+        if current == wanted || tasks.0.iter().any(|(tid, _, _)| tid == id) {
+            continue;
+        }
+
+        tasks.0.push((*id, wanted, spawn_raster(&files, v, wanted)));
+    }
+
+    Ok(())
+}
+
+// swaps in the vector images which have been drawn
+pub fn finish_rasters(
+    mut tasks: ResMut<RasterTasks>,
+    vector_images: Res<VectorImages>,
+    mut scales: ResMut<RasterScales>,
+    mut images: ResMut<Assets<Image>>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>,
+    mut loading_handles: Option<ResMut<LoadingHandles>>
+) -> Result
+{
+    let mut pending = vec![];
+
+    for (id, s, mut task) in tasks.0.drain(..) {
+        let Some(result) = block_on(future::poll_once(&mut task)) else {
+            pending.push((id, s, task));
+            continue;
+        };
+
+        let Some(v) = vector_images.0.get(&id) else { continue; };
+
+        match result {
+            Ok(img) => {
+                // a PDF page has no image until its first drawing
+                images.insert(id, Image::from_dynamic(img, true, RenderAssetUsages::default()))?;
+                rescale_layouts(v, s, &mut layouts);
+                scales.0.insert(id, s);
+            },
+            Err(e) => warn!("cannot draw {:?} at {}x: {e}", v.file, s)
+        }
+
+        // a page which cannot be drawn should not hold up the game
+        if let Some(lh) = loading_handles.as_mut() {
+            lh.0.remove(&id);
+        }
+    }
+
+    tasks.0 = pending;
+    Ok(())
+}
+
+// a reloaded gamebox brings atlases at the gamebox dpi, which must match
+// the scale their images are drawn at now
+pub fn rescale_reloaded_layouts(
+    vector_images: Res<VectorImages>,
+    mut scales: ResMut<RasterScales>,
+    mut layouts: ResMut<Assets<TextureAtlasLayout>>
+)
+{
+    if !vector_images.is_changed() {
+        return;
+    }
+
+    for (id, v) in &vector_images.0 {
+        if let Some(s) = scales.0.get(id) {
+            rescale_layouts(v, *s, &mut layouts);
+        }
+    }
+
+    // sprites are refit to the rescaled atlases
+    scales.set_changed();
+}
+
+// keeps sprites of redrawn vector images at their gamebox size
+pub fn fit_vector_sprites(
+    mut query: Query<&mut Sprite>,
+    vector_images: Res<VectorImages>,
+    scales: Res<RasterScales>,
+    images: Res<Assets<Image>>
+)
+{
+    for mut sprite in query.iter_mut() {
+        if !scales.is_changed() && !sprite.is_changed() {
+            continue;
+        }
+
+        let id = sprite.image.id();
+
+        let size = match scales.0.get(&id) {
+            None | Some(1) => None,
+            Some(s) => match &sprite.texture_atlas {
+                Some(atlas) => vector_images.0.get(&id)
+                    .and_then(|v| v.layouts.iter().find(|(h, _)| h.id() == atlas.layout.id()))
+                    .and_then(|(_, base)| base.textures.get(atlas.index))
+                    .map(|r| r.size().as_vec2()),
+                None => images.get(id)
+                    .map(|img| img.size_f32() / *s as f32)
+            }
+        };
+
+        if sprite.custom_size != size {
+            sprite.custom_size = size;
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use bevy::{
+    app::AppExit,
+    ecs::{
+        change_detection::{Res, ResMut},
+        error::Result,
+        message::MessageReader,
+        observer::On,
+        prelude::Query,
+        world::DeferredWorld
+    },
+    prelude::{debug, Entity, Resource, Time, Timer}
+};
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf}
+};
+
+use crate::{
+    LogPath,
+    log::{CursorMark, EditIndex, Edits, EditsComplete},
+    log_serialize::write_edits_atomic,
+    watch::modified
+};
+
+#[derive(Resource)]
+pub struct RecoveryPath(pub PathBuf);
+
+#[derive(Resource)]
+pub struct ReplayRecovery(pub bool);
+
+// the time since the last autosave, which limits how often it is written
+#[derive(Resource)]
+pub struct AutosaveTimer(pub Timer);
+
+#[derive(Default, Resource)]
+pub struct AutosaveDirty(pub bool);
+
+// where the edit cursor was when the log was last written to or read from
+// its file; None if the edits have never been saved
+#[derive(Default, Resource)]
+pub struct SavedCursor(pub Option<CursorMark>);
+
+pub fn recovery_path(gamebox_path: &Path) -> PathBuf {
+    let mut path = gamebox_path.as_os_str().to_owned();
+    path.push(".recovery.json");
+    PathBuf::from(path)
+}
+
+pub fn recovery_is_newer(recovery: &Path, log: Option<&Path>) -> bool {
+    let Some(recovery_time) = modified(recovery) else { return false; };
+
+    match log.and_then(modified) {
+        Some(log_time) => recovery_time > log_time,
+        None => true
+    }
+}
+
+pub fn offer_recovery(recovery: &Path) -> io::Result<bool> {
+    eprint!(
+        "Found unsaved edits in {}. Replay them? [y/N] ",
+        recovery.display()
+    );
+    io::stderr().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+pub fn remove_recovery_file(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        r => r
+    }
+}
+
+pub fn mark_autosave_dirty(
+    _evt: On<EditsComplete>,
+    mut dirty: ResMut<AutosaveDirty>
+)
+{
+    dirty.0 = true;
+}
+
+pub fn tick_autosave_timer(
+    time: Res<Time>,
+    mut timer: ResMut<AutosaveTimer>
+)
+{
+    timer.0.tick(time.delta());
+}
+
+pub fn autosave_due(
+    dirty: Res<AutosaveDirty>,
+    timer: Res<AutosaveTimer>
+) -> bool
+{
+    // edits which complete while the timer runs are written when it ends
+    dirty.0 && timer.0.is_finished()
+}
+
+pub fn autosave_edits(mut world: DeferredWorld) -> Result {
+    let path = world.resource::<RecoveryPath>().0.clone();
+
+    write_edits_atomic(&world, &path)?;
+    debug!("autosaved log to {}", path.display());
+
+    world.resource_mut::<AutosaveDirty>().0 = false;
+    world.resource_mut::<AutosaveTimer>().0.reset();
+    Ok(())
+}
+
+// a log replayed as it was saved has nothing unsaved
+pub fn mark_loaded_log_saved(
+    log_path: Res<LogPath>,
+    replay_recovery: Res<ReplayRecovery>,
+    cursor_query: Query<(Entity, &Edits, &EditIndex)>,
+    mut saved: ResMut<SavedCursor>
+)
+{
+    if log_path.0.is_some() && !replay_recovery.0 {
+        saved.0 = cursor_query.single()
+            .ok()
+            .map(|(e, edits, idx)| CursorMark::new(e, edits, idx));
+    }
+}
+
+pub fn clear_recovery_on_exit(
+    mut exits: MessageReader<AppExit>,
+    recovery: Res<RecoveryPath>,
+    saved: Res<SavedCursor>,
+    cursor_query: Query<(Entity, &Edits, &EditIndex)>
+) -> Result
+{
+    if exits.read().next().is_none() {
+        return Ok(());
+    }
+
+    let current = cursor_query.single()
+        .ok()
+        .map(|(e, edits, idx)| CursorMark::new(e, edits, idx));
+
+    // unsaved edits are kept for the next start to offer back
+    if current.is_some() && current == saved.0 {
+        remove_recovery_file(&recovery.0)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,125 @@
+use bevy::{
+    color::{
+        Color,
+        palettes::tailwind::{GRAY_50, GRAY_200}
+    },
+    ecs::{
+        change_detection::{Res, ResMut},
+        component::Component,
+        observer::On,
+        prelude::{Commands, Entity, Query, With, Without}
+    },
+    picking::{
+        Pickable,
+        events::{Click, Out, Over, Pointer}
+    },
+    prelude::{AlignItems, BackgroundColor, BorderRadius, Button, children, ChildOf, DespawnOnExit, EntityEvent, FlexDirection, FontSize, JustifyContent, NextState, Node, px, Reflect, Result, Text, TextColor, TextFont, trace, UiRect, Val}
+};
+use std::fmt::Debug;
+use tracing::instrument;
+
+use crate::{
+    gamebox::GameBox,
+    log::{EditOf, Edits, EditsComplete},
+    setup::{ChooseScenario, seed_edits},
+    state::GameState
+};
+
+#[derive(Component)]
+pub struct ScenarioButton(pub usize);
+
+// lists the gamebox's scenarios for the player to pick from
+#[instrument(skip_all)]
+pub fn display_scenarios(
+    gamebox: Res<GameBox>,
+    mut commands: Commands
+)
+{
+    trace!("");
+
+    let bg_color = GRAY_50.into();
+    let highlight_color = GRAY_200.into();
+
+    let font = TextFont {
+        font_size: FontSize::Px(24.0),
+        ..Default::default()
+    };
+
+    let list = commands.spawn((
+        Node {
+            width: Val::Percent(100.0),
+            height: Val::Percent(100.0),
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            flex_direction: FlexDirection::Column,
+            row_gap: Val::Px(8.0),
+            ..Default::default()
+        },
+        children![(
+            Text::new("Choose a scenario"),
+            TextFont {
+                font_size: FontSize::Px(48.0),
+                ..Default::default()
+            }
+        )],
+        DespawnOnExit(GameState::Scenario)
+    )).id();
+
+    for (i, sc) in gamebox.scenario.iter().enumerate() {
+        commands.spawn((
+            ScenarioButton(i),
+            Button,
+            Node {
+                padding: UiRect::all(px(8)),
+                border_radius: BorderRadius::all(px(4)),
+                ..Default::default()
+            },
+            BackgroundColor(bg_color),
+            Pickable::default(),
+            ChildOf(list),
+            children![(
+                Pickable::IGNORE,
+                Text::new(sc.name.clone()),
+                font.clone(),
+                TextColor(Color::BLACK)
+            )]
+        ))
+        .observe(choose_scenario)
+        .observe(recolor_on::<Over>(highlight_color))
+        .observe(recolor_on::<Out>(bg_color));
+    }
+}
+
+fn recolor_on<E: Debug + Clone + Reflect>(
+    color: Color
+) -> impl Fn(On<Pointer<E>>, Query<&mut BackgroundColor, With<ScenarioButton>>)
+{
+    move |evt, mut query| {
+        if let Ok(mut bg) = query.get_mut(evt.event().event_target()) {
+            bg.0 = color;
+        }
+    }
+}
+
+#[instrument(skip_all)]
+pub fn choose_scenario(
+    evt: On<Pointer<Click>>,
+    button_query: Query<&ScenarioButton>,
+    root_query: Query<Entity, (With<Edits>, Without<EditOf>)>,
+    gamebox: Res<GameBox>,
+    mut next: ResMut<NextState<GameState>>,
+    mut commands: Commands
+) -> Result
+{
+    trace!("");
+
+    let ScenarioButton(i) = button_query.get(evt.event().event_target())?;
+    let root = root_query.single()?;
+
+    seed_edits(&gamebox.scenario[*i].surface, &gamebox, root, &mut commands)?;
+    commands.remove_resource::<ChooseScenario>();
+    commands.trigger(EditsComplete);
+
+    next.set(GameState::Game);
+    Ok(())
+}
@@ -270,7 +270,7 @@ pub fn handle_key_selection(
             &a.key,
             Some(ak) if input.just_pressed(ak.code) &&
                 input.modifiers_pressed(&ak.modifiers)
-            ).then_some((entity, a.action))
+            ).then_some((entity, &a.action))
         );
 
     if let Some(ea0) = eai.next() {
@@ -0,0 +1,310 @@
+use bevy::{
+    ecs::{
+        change_detection::Res,
+        prelude::{Commands, Entity, Query, Resource, With, Without}
+    },
+    math::Vec3,
+    prelude::{debug, Result}
+};
+use std::collections::HashMap;
+use tracing::instrument;
+
+use crate::{
+    LogPath,
+    container,
+    edittype::EditType,
+    gamebox::{GameBox, GameBoxDiagnostic, GridDefinition, ScenarioDefinition, SurfaceDefinition},
+    grid,
+    log::{EditOf, Edits, EditsComplete},
+    map,
+    piece,
+    recovery::ReplayRecovery,
+    surface
+};
+
+// the offset of a piece stacked onto another, as when dropped onto it
+const STACK_OFFSET: Vec3 = Vec3::new(2.0, 2.0, 1.0);
+
+// the scenario named on the command line
+#[derive(Resource)]
+pub struct ScenarioName(pub Option<String>);
+
+#[derive(Debug, thiserror::Error)]
+#[error("gamebox has no scenario {name:?}; its scenarios are {}", .known.join(", "))]
+pub struct UnknownScenarioError {
+    pub name: String,
+    pub known: Vec<String>
+}
+
+pub fn find_scenario<'a>(
+    gamebox: &'a GameBox,
+    name: &str
+) -> Result<&'a ScenarioDefinition, UnknownScenarioError>
+{
+    gamebox.scenario.iter()
+        .find(|sc| sc.name == name)
+        .ok_or_else(|| UnknownScenarioError {
+            name: name.into(),
+            known: gamebox.scenario.iter()
+                .map(|sc| format!("{:?}", sc.name))
+                .collect()
+        })
+}
+
+// present while the player has yet to pick a scenario
+#[derive(Resource)]
+pub struct ChooseScenario;
+
+// lays out the gamebox's initial surface as the first edits of a new log
+#[instrument(skip_all)]
+pub fn seed_setup(
+    log_path: Res<LogPath>,
+    replay_recovery: Res<ReplayRecovery>,
+    scenario_name: Res<ScenarioName>,
+    gamebox: Res<GameBox>,
+    root_query: Query<Entity, (With<Edits>, Without<EditOf>)>,
+    mut commands: Commands
+) -> Result
+{
+    debug!("");
+
+    // a log or a recovery file has its own setup
+    if log_path.0.is_some() || replay_recovery.0 {
+        return Ok(());
+    }
+
+    let setup = if let Some(name) = &scenario_name.0 {
+        &find_scenario(&gamebox, name)?.surface
+    }
+    else {
+        match gamebox.scenario.as_slice() {
+            [] => {
+                let Some(surface) = &gamebox.surface else { return Ok(()); };
+                surface
+            },
+            [sc] => &sc.surface,
+            _ => {
+                // the player picks one once loading is done
+                commands.insert_resource(ChooseScenario);
+                return Ok(());
+            }
+        }
+    };
+
+    let root = root_query.single()?;
+    seed_edits(setup, &gamebox, root, &mut commands)?;
+
+    commands.trigger(EditsComplete);
+    Ok(())
+}
+
+// the object ids of a setup's maps, grids and containers, and where each
+// of its pieces goes, in setup order; working these out first keeps a bad
+// setup from leaving half of its edits in the log
+struct SeedPlan {
+    surface_oid: u32,
+    maps: Vec<u32>,
+    grids: Vec<(u32, u32)>,
+    containers: Vec<u32>,
+    pieces: Vec<(u32, u32, Vec3)>
+}
+
+fn plan_seed(
+    setup: &SurfaceDefinition,
+    gamebox: &GameBox
+) -> Result<SeedPlan, GameBoxDiagnostic>
+{
+    let mut next_oid = 0;
+
+    let surface_oid = next_oid;
+    next_oid += 1;
+
+    let mut maps = vec![];
+    let mut map_oids = HashMap::new();
+
+    for &type_id in &setup.map {
+        maps.push(next_oid);
+        map_oids.insert(type_id, next_oid);
+        next_oid += 1;
+    }
+
+    let map_oid = |m: u32| map_oids.get(&m)
+        .copied()
+        .ok_or(GameBoxDiagnostic::SetupUnplacedMap(m));
+
+    let mut grids = vec![];
+    let mut grid_oids = HashMap::new();
+
+    for sg in &setup.grid {
+        let g = gamebox.grid.get(&sg.grid)
+            .ok_or(GameBoxDiagnostic::SetupUnknownGrid(sg.grid))?;
+
+        grids.push((next_oid, sg.map.map_or(Ok(surface_oid), map_oid)?));
+        grid_oids.insert(sg.grid, next_oid);
+        // reserve ids for the cells, too
+        next_oid += 1 + g.cell_count();
+    }
+
+    let mut containers = vec![];
+    let mut container_oids = HashMap::new();
+
+    for &type_id in &setup.container {
+        containers.push(next_oid);
+        container_oids.insert(type_id, next_oid);
+        next_oid += 1;
+    }
+
+    // the top piece in each cell, with its stacking group
+    let mut cell_tops = HashMap::new();
+    let mut pieces = vec![];
+
+    for (i, sp) in setup.piece.iter().enumerate() {
+        let object_id = next_oid;
+        next_oid += 1;
+
+        let p = gamebox.piece.get(&sp.piece)
+            .ok_or(GameBoxDiagnostic::SetupUnknownPiece(sp.piece))?;
+
+        if sp.face >= p.faces.len().max(1) {
+            return Err(GameBoxDiagnostic::SetupUnknownFace {
+                piece: sp.piece,
+                face: sp.face
+            });
+        }
+
+        // later pieces lie above earlier ones
+        let z = 2.0 + i as f32;
+
+        let (parent_id, dst) = match (sp.container, sp.map, sp.grid, sp.cell) {
+            // the container arranges its pieces itself
+            (Some(c), _, _, _) => (
+                container_oids.get(&c)
+                    .copied()
+                    .ok_or(GameBoxDiagnostic::SetupUnplacedContainer(c))?,
+                Vec3::new(0.0, 0.0, z)
+            ),
+            (None, _, None, None) => (
+                sp.map.map_or(Ok(surface_oid), map_oid)?,
+                Vec3::new(sp.x, sp.y, z)
+            ),
+            (None, None, Some(g), Some([col, row])) => {
+                let grid_oid = grid_oids.get(&g)
+                    .copied()
+                    .ok_or(GameBoxDiagnostic::SetupUnplacedGrid(g))?;
+
+                let Some(GridDefinition::Hex(h)) = gamebox.grid.get(&g) else {
+                    return Err(GameBoxDiagnostic::SetupNotAHexGrid {
+                        piece: sp.piece,
+                        grid: g
+                    });
+                };
+
+                if col >= h.cols || row >= h.rows {
+                    return Err(GameBoxDiagnostic::SetupCellOutOfRange {
+                        piece: sp.piece,
+                        grid: g,
+                        col,
+                        row,
+                        cols: h.cols,
+                        rows: h.rows
+                    });
+                }
+
+                let cell_oid = grid_oid + 1 + row * h.cols + col;
+
+                // stack onto what is already in the cell if we can
+                match cell_tops.insert(cell_oid, (object_id, p.stacking_group)) {
+                    Some((top, sg)) if sg == p.stacking_group =>
+                        (top, STACK_OFFSET),
+                    _ => (cell_oid, Vec3::new(0.0, 0.0, z))
+                }
+            },
+            _ => return Err(GameBoxDiagnostic::SetupMalformedCell {
+                piece: sp.piece
+            })
+        };
+
+        pieces.push((object_id, parent_id, dst));
+    }
+
+    Ok(SeedPlan {
+        surface_oid,
+        maps,
+        grids,
+        containers,
+        pieces
+    })
+}
+
+pub fn seed_edits(
+    setup: &SurfaceDefinition,
+    gamebox: &GameBox,
+    root: Entity,
+    commands: &mut Commands
+) -> Result<(), GameBoxDiagnostic>
+{
+    let plan = plan_seed(setup, gamebox)?;
+
+    commands.spawn((
+        EditOf(root),
+        EditType::CreateSurface,
+        surface::create::CreateEdit {
+            object_id: plan.surface_oid,
+            type_id: 0
+        }
+    ));
+
+    for (&type_id, &object_id) in setup.map.iter().zip(&plan.maps) {
+        commands.spawn((
+            EditOf(root),
+            EditType::CreateMap,
+            map::create::CreateEdit {
+                object_id,
+                type_id,
+                parent_id: plan.surface_oid
+            }
+        ));
+    }
+
+    for (sg, &(object_id, parent_id)) in setup.grid.iter().zip(&plan.grids) {
+        commands.spawn((
+            EditOf(root),
+            EditType::CreateGrid,
+            grid::create::CreateEdit {
+                object_id,
+                type_id: sg.grid,
+                parent_id
+            }
+        ));
+    }
+
+    for (&type_id, &object_id) in setup.container.iter().zip(&plan.containers) {
+        commands.spawn((
+            EditOf(root),
+            EditType::CreateContainer,
+            container::create::CreateEdit {
+                object_id,
+                type_id,
+                parent_id: plan.surface_oid
+            }
+        ));
+    }
+
+    for (sp, &(object_id, parent_id, dst)) in setup.piece.iter().zip(&plan.pieces) {
+        commands.spawn((
+            EditOf(root),
+            EditType::Create,
+            piece::create::CreateEdit {
+                object_id,
+                type_id: sp.piece,
+                parent_id,
+                dst,
+                angle: sp.a,
+                anchor: sp.anchor,
+                face: sp.face
+            }
+        ));
+    }
+
+    Ok(())
+}
@@ -5,5 +5,6 @@ use std::hash::Hash;
 pub enum GameState {
     #[default]
     Splash,
+    Scenario,
     Game
 }
@@ -33,7 +33,7 @@ pub struct ForSurface(pub Entity);
 
 pub fn spawn_surface(
     oid: u32,
-    window: Entity,
+    window: Option<Entity>,
     commands: &mut Commands
 )
 {
@@ -52,9 +52,12 @@ pub fn spawn_surface(
     .observe(handle_drop)
     .id();
 
-    commands.entity(window)
-        .insert(ForSurface(id))
-        .observe(forward_dragdrop_to_surface);
+    // there is no window when running headless
+    if let Some(window) = window {
+        commands.entity(window)
+            .insert(ForSurface(id))
+            .observe(forward_dragdrop_to_surface);
+    }
 }
 
 fn forward_dragdrop_to_surface(
@@ -75,7 +75,7 @@ pub fn on_create_undo(
     // get the edit
     let Ok(cr) = edit.get(evt.entity) else { return Ok(()); };
     // get the entity being edited
-    let entity = *objmap.0.get(&cr.object_id).unwrap();
+    let entity = objmap.get(cr.object_id)?;
     // apply the change
     commands.entity(entity).despawn();
     Ok(())
@@ -92,7 +92,7 @@ pub fn on_create_redo(
     // get the edit
     let Ok(cr) = edit.get(evt.entity) else { return Ok(()); };
 
-    let window = q_window.single()?;
+    let window = q_window.single().ok();
 
     // apply the change
     spawn_surface(cr.object_id, window, &mut commands);
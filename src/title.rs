@@ -1,7 +1,7 @@
 use bevy::{
     camera::Camera2d,
     ecs::children,
-    prelude::{AlignItems, Commands, DespawnOnExit, FlexDirection, FontSize, JustifyContent, Node, OrthographicProjection, Projection, Resource, Text, TextFont, Timer, TimerMode, Val}
+    prelude::{AlignItems, Commands, DespawnOnExit, FlexDirection, FontSize, JustifyContent, Node, OrthographicProjection, Projection, Resource, Text, TextFont, Timer, Val}
 };
 
 use crate::state::GameState;
@@ -42,8 +42,4 @@ pub fn display_title(mut commands: Commands) {
         ],
         DespawnOnExit(GameState::Splash),
     ));
-
-    commands.insert_resource(
-        SplashScreenTimer(Timer::from_seconds(2.0, TimerMode::Once))
-    );
 }
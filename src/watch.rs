@@ -0,0 +1,54 @@
+use bevy::prelude::{Timer, TimerMode};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime}
+};
+
+// the modification time of a file, if it exists
+pub fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+}
+
+// polls files for changes to their modification times
+pub struct FileWatch {
+    paths: Vec<PathBuf>,
+    mtimes: Vec<Option<SystemTime>>,
+    timer: Timer
+}
+
+impl FileWatch {
+    pub fn new(paths: Vec<PathBuf>, interval: Duration) -> Self {
+        let mtimes = paths.iter()
+            .map(PathBuf::as_path)
+            .map(modified)
+            .collect();
+
+        FileWatch {
+            paths,
+            mtimes,
+            timer: Timer::new(interval, TimerMode::Repeating)
+        }
+    }
+
+    // returns whether any file appeared, disappeared, or was modified
+    pub fn poll(&mut self, delta: Duration) -> bool {
+        if !self.timer.tick(delta).just_finished() {
+            return false;
+        }
+
+        let mut changed = false;
+
+        for (path, mtime) in self.paths.iter().zip(self.mtimes.iter_mut()) {
+            let now = modified(path);
+            if now != *mtime {
+                *mtime = now;
+                changed = true;
+            }
+        }
+
+        changed
+    }
+}